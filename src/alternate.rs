@@ -0,0 +1,190 @@
+use std::path::{Path, PathBuf};
+
+use eyre::{bail, eyre};
+use fs_tree::FsTree;
+use indexmap::IndexMap;
+
+use crate::{Result, template::TemplateVariables};
+
+/// Separates the name of an alternate from its conditions, like in `.Xresources##host.desk`.
+pub const ALTERNATE_SEPARATOR: &str = "##";
+
+/// Condition attributes, their short forms, the variable they're checked against, and their
+/// weight, so more specific conditions win.
+const ATTRIBUTES: &[(&str, &str, &str, u32)] = &[
+    ("distro", "d", "os_id", 1),
+    ("class", "c", "class", 2),
+    ("host", "h", "hostname", 4),
+    ("user", "u", "username", 8),
+];
+
+/// Splits `name##conditions` into the plain name and its conditions.
+pub fn split_alternate(file_name: &str) -> Option<(&str, &str)> {
+    file_name
+        .split_once(ALTERNATE_SEPARATOR)
+        .filter(|(name, _)| !name.is_empty())
+}
+
+/// Returns how well the conditions match this machine, `None` if any of them doesn't match.
+///
+/// `default` matches everything with the lowest score.
+pub fn alternate_score(conditions: &str, variables: &TemplateVariables) -> Result<Option<u32>> {
+    let mut score = 0;
+
+    for condition in conditions.split(',') {
+        if condition == "default" {
+            continue;
+        }
+
+        let (attribute, expected) = condition
+            .split_once('.')
+            .ok_or_else(|| eyre!("invalid alternate condition {condition:?}"))?;
+
+        let &(_, _, variable, weight) = ATTRIBUTES
+            .iter()
+            .find(|(name, short, ..)| attribute == *name || attribute == *short)
+            .ok_or_else(|| eyre!("unknown alternate condition attribute {attribute:?}"))?;
+
+        if variables.inner.get(variable).map(String::as_str) != Some(expected) {
+            return Ok(None);
+        }
+
+        score += weight;
+    }
+
+    // Even `default` scores, so it's distinguishable from no match
+    Ok(Some(score + 1))
+}
+
+/// Picks the best alternate for each plain path in the group tree, keyed by the plain path.
+///
+/// Plain paths without any matching alternate map to `None`.
+pub fn choose_alternates(
+    group_tree: &FsTree,
+    variables: &TemplateVariables,
+) -> Result<IndexMap<PathBuf, Option<PathBuf>>> {
+    let mut chosen: IndexMap<PathBuf, Option<(u32, PathBuf)>> = IndexMap::new();
+
+    for relative_path in group_tree.paths() {
+        let Some((plain_path, conditions)) = plain_path_of_alternate(&relative_path) else {
+            continue;
+        };
+
+        let score = alternate_score(conditions, variables)
+            .map_err(|err| err.wrap_err(format!("in alternate {relative_path:?}")))?;
+
+        let best = chosen.entry(plain_path).or_default();
+        if let Some(score) = score
+            && best
+                .as_ref()
+                .is_none_or(|(best_score, _)| score > *best_score)
+        {
+            *best = Some((score, relative_path));
+        }
+    }
+
+    Ok(chosen
+        .into_iter()
+        .map(|(plain_path, best)| (plain_path, best.map(|(_, path)| path)))
+        .collect())
+}
+
+/// If `path` is an alternate, returns its plain path and the conditions.
+pub fn plain_path_of_alternate(path: &Path) -> Option<(PathBuf, &str)> {
+    let file_name = path.file_name()?.to_str()?;
+    let (plain_name, conditions) = split_alternate(file_name)?;
+    Some((path.with_file_name(plain_name), conditions))
+}
+
+/// Builds the conditions of an alternate from `attribute=value` pairs, or `default`.
+pub fn alternate_conditions(pairs: &[String]) -> Result<String> {
+    let mut conditions = vec![];
+
+    for pair in pairs {
+        if pair == "default" {
+            conditions.push(pair.clone());
+            continue;
+        }
+
+        let Some((attribute, value)) = pair.split_once('=') else {
+            bail!("invalid alternate {pair:?}, expected `attribute=value` or `default`");
+        };
+
+        if !ATTRIBUTES
+            .iter()
+            .any(|(name, short, ..)| attribute == *name || attribute == *short)
+        {
+            bail!(
+                "unknown alternate attribute {attribute:?}, expected distro, class, host or user"
+            );
+        }
+
+        if value.is_empty() || value.contains([',', '/']) {
+            bail!("invalid alternate value {value:?}");
+        }
+
+        conditions.push(format!("{attribute}.{value}"));
+    }
+
+    Ok(conditions.join(","))
+}
+
+#[cfg(test)]
+mod tests {
+    use fs_tree::tree;
+    use pretty_assertions::assert_eq;
+
+    use super::*;
+
+    fn variables() -> TemplateVariables {
+        TemplateVariables {
+            inner: [("hostname", "desk"), ("username", "me"), ("os_id", "arch")]
+                .map(|(name, value)| (name.to_owned(), value.to_owned()))
+                .into_iter()
+                .collect(),
+        }
+    }
+
+    #[test]
+    fn test_alternate_score() {
+        let variables = variables();
+        let score = |conditions| alternate_score(conditions, &variables).unwrap();
+
+        assert_eq!(score("default"), Some(1));
+        assert_eq!(score("host.laptop"), None);
+        assert!(score("host.desk") > score("distro.arch"));
+        assert!(score("h.desk,d.arch") > score("host.desk"));
+        assert_eq!(score("host.desk,user.other"), None);
+        assert!(alternate_score("os.arch", &variables).is_err());
+    }
+
+    #[test]
+    fn test_choose_alternates() {
+        let group_tree = tree! {
+            ".Xresources##default"
+            ".Xresources##host.desk"
+            ".Xresources##host.laptop"
+            ".config": [
+                "foot.ini##host.laptop"
+            ]
+            plain
+        };
+
+        let chosen = choose_alternates(&group_tree, &variables()).unwrap();
+
+        let expected = [
+            (".Xresources", Some(".Xresources##host.desk")),
+            (".config/foot.ini", None),
+        ]
+        .map(|(plain, chosen)| (PathBuf::from(plain), chosen.map(PathBuf::from)));
+
+        assert_eq!(chosen.into_iter().collect::<Vec<_>>(), expected);
+    }
+
+    #[test]
+    fn test_alternate_conditions() {
+        let pairs = ["host=desk", "user=me"].map(String::from);
+        assert_eq!(alternate_conditions(&pairs).unwrap(), "host.desk,user.me");
+        assert!(alternate_conditions(&["os=arch".to_owned()]).is_err());
+    }
+}
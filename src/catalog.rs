@@ -0,0 +1,237 @@
+use std::path::{self, Path, PathBuf};
+
+use eyre::bail;
+use indexmap::IndexMap;
+
+use crate::Result;
+
+/// Well-known config paths, relative to the base folder, and their canonical group.
+const BUILTIN_CATALOG: &[(&str, &str)] = &[
+    (".alacritty.toml", "alacritty"),
+    (".alacritty.yml", "alacritty"),
+    (".bash_aliases", "bash"),
+    (".bash_profile", "bash"),
+    (".bashrc", "bash"),
+    (".config/Code/User", "vscode"),
+    (".config/alacritty", "alacritty"),
+    (".config/doom", "emacs"),
+    (".config/dunst", "dunst"),
+    (".config/emacs", "emacs"),
+    (".config/fish", "fish"),
+    (".config/fontconfig", "fonts"),
+    (".config/git", "git"),
+    (".config/helix", "helix"),
+    (".config/htop", "htop"),
+    (".config/hypr", "hyprland"),
+    (".config/i3", "i3"),
+    (".config/i3status", "i3"),
+    (".config/kitty", "kitty"),
+    (".config/lf", "lf"),
+    (".config/mpv", "mpv"),
+    (".config/nvim", "nvim"),
+    (".config/picom", "picom"),
+    (".config/polybar", "polybar"),
+    (".config/ranger", "ranger"),
+    (".config/rofi", "rofi"),
+    (".config/starship.toml", "starship"),
+    (".config/sway", "sway"),
+    (".config/systemd/user", "systemd-user"),
+    (".config/tmux", "tmux"),
+    (".config/waybar", "waybar"),
+    (".config/wezterm", "wezterm"),
+    (".config/zsh", "zsh"),
+    (".emacs", "emacs"),
+    (".emacs.d", "emacs"),
+    (".gitconfig", "git"),
+    (".gnupg/gpg-agent.conf", "gnupg"),
+    (".gnupg/gpg.conf", "gnupg"),
+    (".i3", "i3"),
+    (".inputrc", "bash"),
+    (".profile", "shell"),
+    (".ssh/config", "ssh"),
+    (".tmux.conf", "tmux"),
+    (".vim", "vim"),
+    (".vimrc", "vim"),
+    (".wezterm.lua", "wezterm"),
+    (".xinitrc", "x11"),
+    (".xprofile", "x11"),
+    (".Xresources", "x11"),
+    (".zlogin", "zsh"),
+    (".zprofile", "zsh"),
+    (".zshenv", "zsh"),
+    (".zshrc", "zsh"),
+];
+
+/// Maps config paths to group names, for `dotin import --auto`.
+#[derive(Debug, Clone)]
+pub struct Catalog {
+    /// User entries come first, so they win ties against the builtin ones.
+    entries: Vec<(PathBuf, String)>,
+}
+
+impl Catalog {
+    /// The builtin catalog, extended by the `[catalog]` config section.
+    pub fn new(user_entries: &IndexMap<String, String>) -> Self {
+        let user_entries = user_entries
+            .iter()
+            .map(|(path, group)| (PathBuf::from(path), group.clone()));
+        let builtin_entries = BUILTIN_CATALOG
+            .iter()
+            .map(|(path, group)| (PathBuf::from(path), group.to_string()));
+
+        Self {
+            entries: user_entries.chain(builtin_entries).collect(),
+        }
+    }
+
+    /// The group for a path relative to the base folder, the most specific entry wins.
+    ///
+    /// Falls back to the directory name for paths inside of `.config`.
+    pub fn group_for(&self, relative_path: &Path) -> Option<String> {
+        if let Some(group) = self.known_group_for(relative_path) {
+            return Some(group.to_owned());
+        }
+
+        let app = relative_path.strip_prefix(".config").ok()?.iter().next()?;
+        let app = app.to_str()?;
+        if app.starts_with('.') {
+            return None;
+        }
+
+        let group = group_name_for_file(app);
+        (!group.is_empty()).then_some(group)
+    }
+
+    /// Like [`Catalog::group_for`], without the `.config` fallback.
+    pub fn known_group_for(&self, relative_path: &Path) -> Option<&str> {
+        let mut best: Option<&(PathBuf, String)> = None;
+
+        for entry in &self.entries {
+            let is_more_specific = best.is_none_or(|(best_path, _)| {
+                entry.0.components().count() > best_path.components().count()
+            });
+            if relative_path.starts_with(&entry.0) && is_more_specific {
+                best = Some(entry);
+            }
+        }
+
+        best.map(|(_, group)| group.as_str())
+    }
+
+    /// Groups `paths` by the catalog, fails listing the paths it doesn't know.
+    pub fn group_paths(
+        &self,
+        base_folder: &Path,
+        paths: &[PathBuf],
+    ) -> Result<IndexMap<String, Vec<PathBuf>>> {
+        let mut groups: IndexMap<String, Vec<PathBuf>> = IndexMap::new();
+        let mut unknown = vec![];
+
+        for path in paths {
+            let absolute = path::absolute(path)?;
+            let group = absolute
+                .strip_prefix(base_folder)
+                .ok()
+                .and_then(|relative_path| self.group_for(relative_path));
+
+            match group {
+                Some(group) => groups.entry(group).or_default().push(path.clone()),
+                None => unknown.push(path),
+            }
+        }
+
+        if !unknown.is_empty() {
+            bail!(
+                "no known group for {unknown:?}, add them to `[catalog]` in the config, or \
+                 import them with an explicit group name"
+            );
+        }
+
+        Ok(groups)
+    }
+}
+
+/// The group name for a config file or folder not in the catalog, `.npmrc` is `npm`, `foot` is
+/// `foot`, and `mimeapps.list` is `mimeapps`.
+pub fn group_name_for_file(file_name: &str) -> String {
+    let name = file_name.trim_start_matches('.');
+    let name = name.split_once('.').map_or(name, |(stem, _)| stem);
+
+    match name.strip_suffix("rc") {
+        Some(stem) if !stem.is_empty() => stem.to_owned(),
+        _ => name.to_owned(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use pretty_assertions::assert_eq;
+
+    use super::*;
+
+    #[test]
+    fn test_group_for() {
+        let user_entries = [
+            (".config/nvim/lua/work", "work-nvim"),
+            (".gitconfig", "vcs"),
+        ]
+        .map(|(path, group)| (path.to_owned(), group.to_owned()))
+        .into_iter()
+        .collect();
+        let catalog = Catalog::new(&user_entries);
+
+        let group_for = |path: &str| catalog.group_for(Path::new(path));
+        assert_eq!(group_for(".config/nvim").as_deref(), Some("nvim"));
+        assert_eq!(group_for(".config/nvim/init.lua").as_deref(), Some("nvim"));
+        assert_eq!(
+            group_for(".config/nvim/lua/work/init.lua").as_deref(),
+            Some("work-nvim")
+        );
+        assert_eq!(group_for(".gitconfig").as_deref(), Some("vcs"));
+        assert_eq!(group_for(".config/foot/foot.ini").as_deref(), Some("foot"));
+        assert_eq!(
+            group_for(".config/mimeapps.list").as_deref(),
+            Some("mimeapps")
+        );
+        assert_eq!(
+            group_for(".config/fooconf.conf").as_deref(),
+            Some("fooconf")
+        );
+        assert_eq!(group_for(".config/.hidden"), None);
+        assert_eq!(group_for(".unknownrc"), None);
+    }
+
+    #[test]
+    fn test_group_paths() {
+        let catalog = Catalog::new(&IndexMap::new());
+        let paths = [
+            "/home/me/.zshrc",
+            "/home/me/.config/nvim",
+            "/home/me/.zshenv",
+        ]
+        .map(PathBuf::from);
+
+        let groups = catalog.group_paths(Path::new("/home/me"), &paths).unwrap();
+
+        let expected = [
+            ("zsh", vec!["/home/me/.zshrc", "/home/me/.zshenv"]),
+            ("nvim", vec!["/home/me/.config/nvim"]),
+        ]
+        .map(|(group, paths)| {
+            (
+                group.to_owned(),
+                paths.into_iter().map(PathBuf::from).collect::<Vec<_>>(),
+            )
+        });
+        assert_eq!(groups.into_iter().collect::<Vec<_>>(), expected);
+
+        let error = catalog
+            .group_paths(
+                Path::new("/home/me"),
+                &[PathBuf::from("/home/me/.unknownrc")],
+            )
+            .unwrap_err()
+            .to_string();
+        assert!(error.contains("no known group"), "msg = {error}");
+    }
+}
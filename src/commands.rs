@@ -1,6 +1,21 @@
+mod diff;
 mod discard;
 mod import;
 mod link;
+mod regroup;
+mod repair;
+mod scan;
+mod status;
 mod unlink;
 
-pub use self::{discard::discard, import::import, link::link, unlink::unlink};
+pub use self::{
+    diff::{DiffEntry, Difference, diff, group_diff},
+    discard::{DiscardOptions, discard, plan_discard_all},
+    import::{ImportOptions, import},
+    link::{LinkOptions, link},
+    regroup::{merge_groups, move_paths, rename_group, split_group},
+    repair::repair,
+    scan::{ScanCandidate, find_unmanaged, scan},
+    status::status,
+    unlink::{UnlinkOptions, unlink},
+};
@@ -0,0 +1,502 @@
+use std::{
+    fmt,
+    path::{self, Path, PathBuf},
+};
+
+use eyre::{WrapErr, bail};
+use fs_err as fs;
+use fs_tree::FsTree;
+use similar::TextDiff;
+
+use crate::{
+    Result,
+    alternate::{choose_alternates, plain_path_of_alternate},
+    template::{TemplateVariables, render_file, template_output_path},
+    utils::{
+        FileType, are_equivalent_symlink_targets, create_relative_symlink_target_path,
+        files_match_content, read_file_type, rewrite_symlink_target, try_exists,
+    },
+};
+
+/// How a group file differs from what is at its place in the base folder.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Difference {
+    /// Nothing at the base folder.
+    Missing,
+    /// Unified diff from the base folder version to the group one.
+    Text(String),
+    /// Content differs, and at least one side isn't text.
+    Binary {
+        base: ContentSummary,
+        group: ContentSummary,
+    },
+    /// Both are symlinks, with targets resolving to different paths.
+    SymlinkTarget { base: PathBuf, group: PathBuf },
+    /// Different file types, with a description of each side.
+    Type { base: String, group: String },
+}
+
+/// Size and hash of a binary file, to tell versions apart.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ContentSummary {
+    pub size: usize,
+    pub hash: u64,
+}
+
+impl ContentSummary {
+    fn of(content: &[u8]) -> Self {
+        Self {
+            size: content.len(),
+            hash: rapidhash::v3::rapidhash_v3(content),
+        }
+    }
+}
+
+/// A path of the group that differs from the base folder.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DiffEntry {
+    /// Path relative to the base folder.
+    pub path: PathBuf,
+    pub difference: Difference,
+    /// The group file, if its path differs from `path` (templates and alternates).
+    pub source: Option<PathBuf>,
+}
+
+impl fmt::Display for DiffEntry {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}: {:?}", self.difference, self.path)?;
+        if let Some(source) = &self.source {
+            write!(f, " (from {source:?})")?;
+        }
+        Ok(())
+    }
+}
+
+impl fmt::Display for Difference {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Difference::Missing => write!(f, "missing at the base folder"),
+            Difference::Text(_) => write!(f, "text differs"),
+            Difference::Binary { base, group } => {
+                write!(
+                    f,
+                    "binary content differs, {base} at the base folder, {group} in the group"
+                )
+            }
+            Difference::SymlinkTarget { base, group } => {
+                write!(f, "symlink points to {base:?} instead of {group:?}")
+            }
+            Difference::Type { base, group } => {
+                write!(f, "{base} at the base folder, {group} in the group")
+            }
+        }
+    }
+}
+
+impl fmt::Display for ContentSummary {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} bytes (hash {:016x})", self.size, self.hash)
+    }
+}
+
+/// Prints how the group files differ from what is at the base folder, only for `paths` if any
+/// are given, returns whether anything differs.
+pub fn diff(
+    base_dir: &Path,
+    group_dir: &Path,
+    paths: &[PathBuf],
+    variables: &TemplateVariables,
+) -> Result<bool> {
+    let entries = group_diff(base_dir, group_dir, paths, variables)?;
+
+    if entries.is_empty() {
+        println!("No differences.");
+    }
+    for entry in &entries {
+        println!("{entry}");
+        if let Difference::Text(diff) = &entry.difference {
+            print!("{diff}");
+        }
+    }
+
+    Ok(!entries.is_empty())
+}
+
+/// Group paths that differ from the base folder, walking the group like `link` does, and
+/// comparing templates by rendering them.
+pub fn group_diff(
+    base_dir: &Path,
+    group_dir: &Path,
+    paths: &[PathBuf],
+    variables: &TemplateVariables,
+) -> Result<Vec<DiffEntry>> {
+    let filters = paths
+        .iter()
+        .map(|path| base_relative_path(path, base_dir, group_dir))
+        .collect::<Result<Vec<_>>>()?;
+    let is_selected =
+        |path: &Path| filters.is_empty() || filters.iter().any(|filter| path.starts_with(filter));
+
+    let group_tree = FsTree::symlink_read_at(group_dir).wrap_err("reading dotfiles folder tree")?;
+    let alternates = choose_alternates(&group_tree, variables)?;
+
+    let mut entries = vec![];
+    // alternates, and paths with something else in the way, are compared as a whole
+    let mut compared = vec![];
+
+    for (group_node, relative_path) in group_tree.iter().min_depth(1) {
+        if compared
+            .iter()
+            .any(|compared_path| relative_path.starts_with(compared_path))
+        {
+            continue;
+        }
+
+        let dotfile_absolute = group_dir.join(&relative_path);
+
+        if let Some((plain_path, _)) = plain_path_of_alternate(&relative_path) {
+            compared.push(relative_path.clone());
+
+            let chosen = alternates.get(&plain_path).and_then(Option::as_ref);
+            if chosen == Some(&relative_path)
+                && (is_selected(&plain_path) || is_selected(&relative_path))
+            {
+                diff_path(
+                    &base_dir.join(&plain_path),
+                    &dotfile_absolute,
+                    &plain_path,
+                    Some(&relative_path),
+                    &mut entries,
+                )?;
+            }
+            continue;
+        }
+
+        if group_node.is_regular()
+            && let Some(output_relative_path) = template_output_path(&relative_path)
+        {
+            if !is_selected(&output_relative_path) && !is_selected(&relative_path) {
+                continue;
+            }
+
+            let rendered = render_file(&dotfile_absolute, variables)?;
+            let output_absolute = base_dir.join(&output_relative_path);
+            if let Some(difference) =
+                rendered_difference(&output_absolute, &dotfile_absolute, &rendered)?
+            {
+                entries.push(DiffEntry {
+                    path: output_relative_path,
+                    difference,
+                    source: Some(relative_path),
+                });
+            }
+            continue;
+        }
+
+        let base_absolute = base_dir.join(&relative_path);
+        let is_blocked = !group_node.is_leaf()
+            && try_exists(&base_absolute)?
+            && read_file_type(&base_absolute)? != FileType::Directory;
+
+        if is_blocked {
+            compared.push(relative_path.clone());
+        }
+        if (group_node.is_leaf() || is_blocked) && is_selected(&relative_path) {
+            diff_path(
+                &base_absolute,
+                &dotfile_absolute,
+                &relative_path,
+                None,
+                &mut entries,
+            )?;
+        }
+    }
+
+    entries.sort_by(|a, b| a.path.cmp(&b.path));
+    Ok(entries)
+}
+
+/// Compares the group file at `dotfile_absolute` with what is at `base_absolute`, recursing into
+/// directories at both sides.
+fn diff_path(
+    base_absolute: &Path,
+    dotfile_absolute: &Path,
+    path: &Path,
+    source: Option<&Path>,
+    entries: &mut Vec<DiffEntry>,
+) -> Result<()> {
+    let mut push = |difference| {
+        entries.push(DiffEntry {
+            path: path.to_owned(),
+            difference,
+            source: source.map(Path::to_owned),
+        });
+    };
+
+    if !try_exists(base_absolute)? {
+        push(Difference::Missing);
+        return Ok(());
+    }
+
+    let base_type = read_file_type(base_absolute)?;
+    let group_type = read_file_type(dotfile_absolute)?;
+
+    if base_type == FileType::Symlink {
+        let symlink_target = create_relative_symlink_target_path(base_absolute, dotfile_absolute);
+        let current_target = fs::read_link(base_absolute)?;
+        if are_equivalent_symlink_targets(base_absolute, &current_target, &symlink_target) {
+            return Ok(());
+        }
+    }
+
+    use FileType::*;
+    match (base_type, group_type) {
+        (Regular, Regular) => {
+            if !files_match_content(base_absolute, dotfile_absolute)? {
+                push(content_difference(
+                    &fs::read(base_absolute)?,
+                    &fs::read(dotfile_absolute)?,
+                    base_absolute,
+                    dotfile_absolute,
+                ));
+            }
+        }
+        (Symlink, Symlink) => {
+            let base_target = fs::read_link(base_absolute)?;
+            let group_target = fs::read_link(dotfile_absolute)?;
+            let expected_target =
+                rewrite_symlink_target(&group_target, dotfile_absolute, base_absolute);
+
+            if !are_equivalent_symlink_targets(base_absolute, &base_target, &expected_target) {
+                push(Difference::SymlinkTarget {
+                    base: base_target,
+                    group: expected_target,
+                });
+            }
+        }
+        (Directory, Directory) => {
+            for entry in fs::read_dir(dotfile_absolute)? {
+                let name = entry?.file_name();
+                diff_path(
+                    &base_absolute.join(&name),
+                    &dotfile_absolute.join(&name),
+                    &path.join(&name),
+                    source.map(|source| source.join(&name)).as_deref(),
+                    entries,
+                )?;
+            }
+        }
+        _ => push(Difference::Type {
+            base: describe(base_absolute, base_type)?,
+            group: describe(dotfile_absolute, group_type)?,
+        }),
+    }
+
+    Ok(())
+}
+
+/// Compares the rendered template with its output at the base folder.
+fn rendered_difference(
+    output_absolute: &Path,
+    template_absolute: &Path,
+    rendered: &str,
+) -> Result<Option<Difference>> {
+    if !try_exists(output_absolute)? {
+        return Ok(Some(Difference::Missing));
+    }
+
+    let output_type = read_file_type(output_absolute)?;
+    if output_type != FileType::Regular {
+        return Ok(Some(Difference::Type {
+            base: describe(output_absolute, output_type)?,
+            group: format!("rendered template of {} bytes", rendered.len()),
+        }));
+    }
+
+    let output = fs::read(output_absolute)?;
+    if output == rendered.as_bytes() {
+        return Ok(None);
+    }
+
+    Ok(Some(content_difference(
+        &output,
+        rendered.as_bytes(),
+        output_absolute,
+        template_absolute,
+    )))
+}
+
+/// A unified diff if both sides are text, sizes and hashes otherwise.
+fn content_difference(
+    base: &[u8],
+    group: &[u8],
+    base_path: &Path,
+    group_path: &Path,
+) -> Difference {
+    let is_text = |content: &[u8]| !content.contains(&0);
+
+    match (std::str::from_utf8(base), std::str::from_utf8(group)) {
+        (Ok(base_text), Ok(group_text)) if is_text(base) && is_text(group) => {
+            let diff = TextDiff::from_lines(base_text, group_text)
+                .unified_diff()
+                .header(&base_path.to_string_lossy(), &group_path.to_string_lossy())
+                .to_string();
+            Difference::Text(diff)
+        }
+        _ => Difference::Binary {
+            base: ContentSummary::of(base),
+            group: ContentSummary::of(group),
+        },
+    }
+}
+
+/// Type of the file at `path`, with its size, entry count or target.
+fn describe(path: &Path, file_type: FileType) -> Result<String> {
+    Ok(match file_type {
+        FileType::Regular => format!(
+            "regular file of {} bytes",
+            fs::symlink_metadata(path)?.len()
+        ),
+        FileType::Directory => format!("directory with {} entries", fs::read_dir(path)?.count()),
+        FileType::Symlink => format!("symlink to {:?}", fs::read_link(path)?),
+    })
+}
+
+/// The path relative to the base folder for an argument, which can be a path inside of the
+/// group folder, a path at the base folder, or already relative.
+fn base_relative_path(path: &Path, base_dir: &Path, group_dir: &Path) -> Result<PathBuf> {
+    let absolute = path::absolute(path)?;
+
+    if let Ok(relative_path) = absolute.strip_prefix(group_dir) {
+        Ok(relative_path.to_owned())
+    } else if let Ok(relative_path) = absolute.strip_prefix(base_dir) {
+        Ok(relative_path.to_owned())
+    } else if path.is_relative() {
+        Ok(path.to_owned())
+    } else {
+        bail!("{path:?} isn't inside of the base folder {base_dir:?} nor the group {group_dir:?}")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use fs_tree::tree;
+    use pretty_assertions::assert_eq;
+
+    use super::*;
+    use crate::utils::test_utils::cd_to_testdir;
+
+    #[test]
+    fn test_group_diff() {
+        let (_dropper, test_dir) = cd_to_testdir().unwrap();
+
+        tree! {
+            linked -> "dotfiles/group/linked"
+            same
+            text
+            binary
+            blocked: []
+            alternate
+            copied_dir: [
+                same
+                text
+            ]
+            link -> "target"
+            other_link -> "elsewhere"
+            stale
+            dotfiles: [
+                group: [
+                    linked
+                    same
+                    text
+                    binary
+                    blocked
+                    missing
+                    "alternate##host.desk"
+                    "alternate##default"
+                    "copied_dir##default": [
+                        same
+                        text
+                        missing
+                    ]
+                    link -> "../../target"
+                    other_link -> "../../target"
+                    "fresh.dotin.tmpl"
+                    "stale.dotin.tmpl"
+                ]
+            ]
+        }
+        .write_structure_at(".")
+        .unwrap();
+
+        for (path, content) in [
+            ("same", "same\n"),
+            ("dotfiles/group/same", "same\n"),
+            ("text", "a\nb\n"),
+            ("dotfiles/group/text", "a\nc\n"),
+            ("binary", "\0one"),
+            ("dotfiles/group/binary", "\0two"),
+            ("alternate", "desk\n"),
+            ("dotfiles/group/alternate##host.desk", "desk\n"),
+            ("copied_dir/text", "old\n"),
+            ("dotfiles/group/copied_dir##default/text", "new\n"),
+            ("dotfiles/group/fresh.dotin.tmpl", "host = {{ hostname }}"),
+            ("dotfiles/group/stale.dotin.tmpl", "host = {{ hostname }}"),
+            ("fresh", "host = desk"),
+            ("stale", "host = laptop"),
+        ] {
+            fs::write(path, content).unwrap();
+        }
+
+        let mut variables = TemplateVariables::default();
+        variables.inner.insert("hostname".into(), "desk".into());
+        let group_dir = test_dir.join("dotfiles/group");
+
+        let result = group_diff(test_dir, &group_dir, &[], &variables).unwrap();
+        let summary = result
+            .iter()
+            .map(|entry| entry.to_string())
+            .collect::<Vec<_>>();
+
+        let binary_summary = |content: &[u8]| ContentSummary::of(content).to_string();
+        let expected = [
+            format!(
+                "binary content differs, {} at the base folder, {} in the group: \"binary\"",
+                binary_summary(b"\0one"),
+                binary_summary(b"\0two"),
+            ),
+            r#"directory with 0 entries at the base folder, regular file of 0 bytes in the group: "blocked""#.to_owned(),
+            r#"missing at the base folder: "copied_dir/missing" (from "copied_dir##default/missing")"#.to_owned(),
+            r#"text differs: "copied_dir/text" (from "copied_dir##default/text")"#.to_owned(),
+            r#"missing at the base folder: "missing""#.to_owned(),
+            r#"symlink points to "elsewhere" instead of "target": "other_link""#.to_owned(),
+            r#"text differs: "stale" (from "stale.dotin.tmpl")"#.to_owned(),
+            r#"text differs: "text""#.to_owned(),
+        ];
+        assert_eq!(summary, expected);
+
+        let Difference::Text(diff) = &result.last().unwrap().difference else {
+            panic!("expected a text diff");
+        };
+        assert!(
+            diff.ends_with("@@ -1,2 +1,2 @@\n a\n-b\n+c\n"),
+            "diff = {diff}"
+        );
+
+        let selected = group_diff(
+            test_dir,
+            &group_dir,
+            &[test_dir.join("copied_dir"), PathBuf::from("stale")],
+            &variables,
+        )
+        .unwrap();
+        let selected_paths = selected
+            .iter()
+            .map(|entry| entry.path.to_str().unwrap())
+            .collect::<Vec<_>>();
+        assert_eq!(
+            selected_paths,
+            ["copied_dir/missing", "copied_dir/text", "stale"]
+        );
+    }
+}
@@ -1,11 +1,12 @@
 use std::path::{self, Path, PathBuf};
 
-use eyre::{WrapErr, eyre};
+use eyre::{WrapErr, bail, eyre};
 use fs_err as fs;
 
 use crate::{
     Result,
-    utils::{FileType, PathTrie, cheap_move_with_fallback, read_file_type, try_exists},
+    conflict::{ConflictPolicy, ConflictResolution, Operation, resolve_conflict},
+    utils::{self, FileType, PathTrie, cheap_move_with_fallback, read_file_type, try_exists},
 };
 
 #[derive(Debug)]
@@ -13,20 +14,20 @@ struct FileToDiscard {
     user_given_path: PathBuf,
     absolute_dotfile_path: PathBuf,
     equivalent_home_path: PathBuf,
-    conflict_resolution: DiscardConflictResolution,
+    conflict_resolution: ConflictResolution,
 }
 
-#[derive(Clone, Copy, Debug)]
-enum DiscardConflictResolution {
-    None,
-    DeleteDir,
-    DeleteSymlink,
+#[derive(Debug, Default, Clone)]
+pub struct DiscardOptions {
+    /// Ask how to resolve conflicts instead of failing.
+    pub interactive: bool,
 }
 
 fn prepare_discard_and_run_checks(
     path: &Path,
     base_dir: &Path,
     absolute_group_path: &Path,
+    interactive: bool,
 ) -> Result<FileToDiscard> {
     let absolute = path::absolute(path)?;
 
@@ -71,78 +72,16 @@ fn prepare_discard_and_run_checks(
 
     let equivalent_home_path = base_dir.join(&relative_path_piece);
 
-    let conflict_resolution = 'conflict_check: {
-        // TODO: add check for conflict where `from` and `to` types mismatch
-        // and just err.
-
-        // If file already exists at home, check if it's a scenario of conflict error
-        if try_exists(&equivalent_home_path)? {
-            let file_type = read_file_type(&equivalent_home_path)?;
-
-            if let (a, b) = (
-                read_file_type(&absolute_dotfile_path)?,
-                read_file_type(&equivalent_home_path)?,
-            ) && a != b
-                && b != FileType::Directory
-                && b != FileType::Symlink
-            {
-                return Err(eyre!(
-                    "can't discard {path:?}, it conflicts with {equivalent_home_path:?}, \
-                     and their types are different",
-                )
-                .wrap_err(format!("{path:?} has type {a}")))
-                .wrap_err(format!("{equivalent_home_path:?} has type {b}"));
-            }
-
-            match file_type {
-                FileType::Regular => {
-                    return Err(eyre!(
-                        "file at {:?} already exists, so {:?} cannot be discarded to that place",
-                        equivalent_home_path,
-                        path,
-                    ));
-                }
-                FileType::Directory => {
-                    // Allow discarding if there is an empty directory at the same place
-                    let is_empty_dir = fs::read_dir(&equivalent_home_path)?.next().is_none();
-                    if is_empty_dir {
-                        break 'conflict_check DiscardConflictResolution::DeleteDir;
-                    }
-
-                    return Err(eyre!(
-                        "non-empty directory at {:?} already exists, couldn't discard {:?}",
-                        equivalent_home_path,
-                        path,
-                    ));
-                }
-                FileType::Symlink => {
-                    let target = fs::read_link(&equivalent_home_path)?;
-                    let absolute_target = if target.is_absolute() {
-                        target.clone()
-                    } else {
-                        equivalent_home_path
-                            .parent()
-                            .unwrap_or(Path::new("."))
-                            .join(&target)
-                    };
-
-                    // Allow discarding into a symlink if it's pointing to the same file we're discarding
-                    // (likely linked by dotin itself)
-                    if let Ok(canonicalized) = fs::canonicalize(&absolute_target)
-                        && canonicalized == absolute_dotfile_path
-                    {
-                        break 'conflict_check DiscardConflictResolution::DeleteSymlink;
-                    }
-
-                    return Err(eyre!(
-                        "there is a symlink at {equivalent_home_path:?}, but it points to {target:?} and not {absolute_dotfile_path:?}"
-                    ));
-                }
-            }
-        }
-
-        DiscardConflictResolution::None
+    let policy = ConflictPolicy {
+        interactive,
+        ..ConflictPolicy::default()
     };
+    let conflict_resolution = resolve_conflict(
+        &absolute_dotfile_path,
+        &equivalent_home_path,
+        Operation::Discard,
+        &policy,
+    )?;
 
     Ok(FileToDiscard {
         user_given_path: path.to_owned(),
@@ -152,11 +91,23 @@ fn prepare_discard_and_run_checks(
     })
 }
 
-pub fn discard(base_dir: &Path, absolute_group_path: &Path, paths: &[PathBuf]) -> Result<()> {
+pub fn discard(
+    base_dir: &Path,
+    absolute_group_path: &Path,
+    paths: &[PathBuf],
+    options: &DiscardOptions,
+) -> Result<()> {
     let files_to_discard = {
         let mut files: Vec<FileToDiscard> = paths
             .iter()
-            .map(|path| prepare_discard_and_run_checks(path, base_dir, absolute_group_path))
+            .map(|path| {
+                prepare_discard_and_run_checks(
+                    path,
+                    base_dir,
+                    absolute_group_path,
+                    options.interactive,
+                )
+            })
             .collect::<Result<_>>()?;
 
         if files.is_empty() {
@@ -189,14 +140,13 @@ pub fn discard(base_dir: &Path, absolute_group_path: &Path, paths: &[PathBuf]) -
     for file in files_to_discard {
         assert!(try_exists(&file.absolute_dotfile_path).is_ok());
 
-        match file.conflict_resolution {
-            DiscardConflictResolution::None => {}
-            DiscardConflictResolution::DeleteDir => {
-                fs::remove_dir(&file.equivalent_home_path)?;
-            }
-            DiscardConflictResolution::DeleteSymlink => {
-                fs::remove_file(&file.equivalent_home_path)?;
+        let resolution = &file.conflict_resolution;
+        if !resolution.prepare(&file.absolute_dotfile_path, &file.equivalent_home_path)? {
+            // the base folder already has it, so the group copy isn't needed
+            if *resolution == ConflictResolution::Identical {
+                utils::remove_path(&file.absolute_dotfile_path)?;
             }
+            continue;
         }
 
         cheap_move_with_fallback(&file.absolute_dotfile_path, &file.equivalent_home_path)
@@ -206,6 +156,48 @@ pub fn discard(base_dir: &Path, absolute_group_path: &Path, paths: &[PathBuf]) -
     Ok(())
 }
 
+/// The paths to discard a whole group, its entries, descending into directories that already
+/// exist at the base folder (linked file by file).
+pub fn plan_discard_all(base_dir: &Path, absolute_group_path: &Path) -> Result<Vec<PathBuf>> {
+    if !try_exists(absolute_group_path)? {
+        bail!("group folder {absolute_group_path:?} doesn't exist");
+    }
+
+    let mut paths = vec![];
+    collect_group_entries(base_dir, absolute_group_path, Path::new(""), &mut paths)?;
+    Ok(paths)
+}
+
+fn collect_group_entries(
+    base_dir: &Path,
+    absolute_group_path: &Path,
+    relative_dir: &Path,
+    paths: &mut Vec<PathBuf>,
+) -> Result<()> {
+    let mut entries = fs::read_dir(absolute_group_path.join(relative_dir))?
+        .map(|entry| Ok(relative_dir.join(entry?.file_name())))
+        .collect::<Result<Vec<_>>>()?;
+    entries.sort();
+
+    for relative_path in entries {
+        let group_path = absolute_group_path.join(&relative_path);
+        let home_path = base_dir.join(&relative_path);
+
+        let is_dir_at_both = read_file_type(&group_path)? == FileType::Directory
+            && try_exists(&home_path)?
+            && read_file_type(&home_path)? == FileType::Directory
+            && fs::read_dir(&home_path)?.next().is_some();
+
+        if is_dir_at_both {
+            collect_group_entries(base_dir, absolute_group_path, &relative_path, paths)?;
+        } else {
+            paths.push(group_path);
+        }
+    }
+
+    Ok(())
+}
+
 #[cfg(test)]
 pub mod tests {
     use fs_tree::tree;
@@ -230,17 +222,19 @@ pub mod tests {
 
         home.write_structure_at(".").unwrap();
         dotfiles.write_structure_at(".").unwrap();
+        fs::write("discarded_path", "home").unwrap();
+        fs::write("dotfiles/example_group/discarded_path", "group").unwrap();
 
         let error_message = discard(
             test_dir,
             &test_dir.join("dotfiles/example_group"),
             ["discarded_path"].map(PathBuf::from).as_slice(),
+            &DiscardOptions::default(),
         )
         .unwrap_err()
         .to_string();
 
-        assert!(error_message.contains("already exists, so"));
-        assert!(error_message.contains("cannot be discarded to that place"));
+        assert!(error_message.contains("content is different"));
     }
 
     #[test]
@@ -273,6 +267,7 @@ pub mod tests {
             test_dir,
             &test_dir.join("dotfiles/example_group"),
             ["discarded_path"].map(PathBuf::from).as_slice(),
+            &DiscardOptions::default(),
         )
         .unwrap();
 
@@ -282,6 +277,44 @@ pub mod tests {
         assert_eq!(dotfiles_result, expected_dotfiles);
     }
 
+    #[test]
+    fn test_discard_succeeds_conflict_with_identical_file() {
+        let (_dropper, test_dir) = cd_to_testdir().unwrap();
+        let home = tree! {
+            discarded_path
+        };
+        let dotfiles = tree! {
+            dotfiles: [
+                example_group: [
+                    discarded_path
+                ]
+            ]
+        };
+
+        let expected_dotfiles = tree! {
+            dotfiles: [
+                example_group: []
+            ]
+        };
+
+        home.write_structure_at(".").unwrap();
+        dotfiles.write_structure_at(".").unwrap();
+        fs::write("discarded_path", "same").unwrap();
+        fs::write("dotfiles/example_group/discarded_path", "same").unwrap();
+
+        discard(
+            test_dir,
+            &test_dir.join("dotfiles/example_group"),
+            ["discarded_path"].map(PathBuf::from).as_slice(),
+            &DiscardOptions::default(),
+        )
+        .unwrap();
+
+        assert_eq!(fs::read_to_string("discarded_path").unwrap(), "same");
+        let dotfiles_result = expected_dotfiles.symlink_read_structure_at(".").unwrap();
+        assert_eq!(dotfiles_result, expected_dotfiles);
+    }
+
     #[test]
     fn test_discard_fails_conflict_with_non_empty_dir() {
         let (_dropper, test_dir) = cd_to_testdir().unwrap();
@@ -305,12 +338,12 @@ pub mod tests {
             test_dir,
             &test_dir.join("dotfiles/example_group"),
             ["discarded_path"].map(PathBuf::from).as_slice(),
+            &DiscardOptions::default(),
         )
         .unwrap_err()
         .to_string();
 
-        assert!(error_message.contains("non-empty directory at"));
-        assert!(error_message.contains("already exists, couldn't discard"));
+        assert!(error_message.contains("there is a non-empty directory at"));
     }
 
     #[test]
@@ -346,6 +379,7 @@ pub mod tests {
             test_dir,
             &test_dir.join("dotfiles/example_group"),
             ["discarded_path"].map(PathBuf::from).as_slice(),
+            &DiscardOptions::default(),
         )
         .unwrap();
 
@@ -398,6 +432,7 @@ pub mod tests {
             &base_dir,
             &test_dir.join("dotfiles/sddm"),
             ["etc/config"].map(PathBuf::from).as_slice(),
+            &DiscardOptions::default(),
         )
         .unwrap();
 
@@ -430,6 +465,7 @@ pub mod tests {
             test_dir,
             &test_dir.join("dotfiles/example_group"),
             ["discarded_path"].map(PathBuf::from).as_slice(),
+            &DiscardOptions::default(),
         )
         .unwrap_err()
         .to_string();
@@ -526,6 +562,7 @@ pub mod tests {
             test_dir,
             &test_dir.join("dotfiles/group_name"),
             &files_to_discard,
+            &DiscardOptions::default(),
         )
         .unwrap();
 
@@ -574,6 +611,7 @@ pub mod tests {
             ["dir/parent", "dir/parent/file"]
                 .map(PathBuf::from)
                 .as_slice(),
+            &DiscardOptions::default(),
         )
         .unwrap();
 
@@ -612,6 +650,7 @@ pub mod tests {
             test_dir,
             &test_dir.join("dotfiles/group"),
             ["link"].map(PathBuf::from).as_slice(),
+            &DiscardOptions::default(),
         )
         .unwrap();
 
@@ -620,4 +659,57 @@ pub mod tests {
         let dotfiles_result = expected_dotfiles.symlink_read_structure_at(".").unwrap();
         assert_eq!(dotfiles_result, expected_dotfiles);
     }
+
+    #[test]
+    fn test_discard_all() {
+        let (_dropper, test_dir) = cd_to_testdir().unwrap();
+        let group_dir = test_dir.join("dotfiles/nvim");
+
+        tree! {
+            ".config": [
+                nvim: [
+                    "init.lua" -> "../../dotfiles/nvim/.config/nvim/init.lua"
+                ]
+                other
+            ]
+            ".nvimrc" -> "dotfiles/nvim/.nvimrc"
+            dotfiles: [
+                nvim: [
+                    ".config": [
+                        nvim: [
+                            "init.lua"
+                        ]
+                    ]
+                    ".nvimrc"
+                ]
+            ]
+        }
+        .write_structure_at(".")
+        .unwrap();
+
+        let paths = plan_discard_all(test_dir, &group_dir).unwrap();
+        assert_eq!(
+            paths,
+            [
+                group_dir.join(".config/nvim/init.lua"),
+                group_dir.join(".nvimrc"),
+            ]
+        );
+
+        discard(test_dir, &group_dir, &paths, &DiscardOptions::default()).unwrap();
+        assert!(utils::remove_empty_group_folder(&group_dir).unwrap());
+
+        let expected = tree! {
+            ".config": [
+                nvim: [
+                    "init.lua"
+                ]
+                other
+            ]
+            ".nvimrc"
+            dotfiles: []
+        };
+        let result = expected.symlink_read_structure_at(".").unwrap();
+        assert_eq!(result, expected);
+    }
 }
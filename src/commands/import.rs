@@ -1,37 +1,66 @@
 use std::{
-    io::{self, BufRead, BufReader, Read},
+    io,
     path::{self, Path, PathBuf},
 };
 
-use eyre::{WrapErr, eyre};
+use eyre::{WrapErr, bail, eyre};
 use fs_err as fs;
+use fs_tree::FsTree;
+use glob::Pattern;
 
 use crate::{
     Result,
-    utils::{self, FileType, cheap_move_with_fallback, read_file_type, try_exists},
+    alternate::ALTERNATE_SEPARATOR,
+    conflict::{ConflictPolicy, ConflictResolution, Operation, resolve_conflict},
+    utils::{
+        self, FileType, cheap_move_with_fallback, read_file_type, rewrite_symlink_target,
+        try_exists,
+    },
 };
 
 #[derive(Debug)]
 struct FileToMove<'a> {
     path: &'a Path,
     to_path: PathBuf,
-    conflict_resolution: ImportConflictResolution,
+    kind: ImportKind,
+    conflict_resolution: ConflictResolution,
 }
 
-// TODO: unify conflict resolution of import with discard, reuse some of the code
-#[derive(Clone, Copy, Debug)]
-enum ImportConflictResolution {
-    None,
-    DeleteRegularFile,
-    DeleteDir,
-    SkipThis,
+#[derive(Debug)]
+enum ImportKind {
+    Move,
+    /// Recreate the symlink in the group with this target, then remove it.
+    RewriteSymlink(PathBuf),
+    /// Copy the target of the symlink (this path) into the group, then remove the symlink.
+    Dereference(PathBuf),
+}
+
+#[derive(Debug, Default, Clone)]
+pub struct ImportOptions {
+    /// Import files as alternates with these conditions, like `host.desk`.
+    pub alternate: Option<String>,
+    /// Leave files matching these patterns at the base folder, matched against the path
+    /// relative to the imported argument, or relative to the base folder.
+    pub exclude: Vec<Pattern>,
+    /// Ask how to resolve conflicts instead of failing.
+    pub interactive: bool,
+    /// Import the content of symlinks' targets, instead of the symlinks (with their targets
+    /// rewritten for the new location).
+    pub dereference: bool,
 }
 
-pub fn import(base_path: &Path, absolute_group_path: &Path, files: &[PathBuf]) -> Result<()> {
+pub fn import(
+    base_path: &Path,
+    absolute_group_path: &Path,
+    files: &[PathBuf],
+    options: &ImportOptions,
+) -> Result<()> {
     let dotfiles_folder = absolute_group_path
         .parent()
         .expect("Internal error, malformed dotfiles folder");
 
+    let files = &expand_file_arguments(base_path, files, &options.exclude)?;
+
     let absolute_paths: Vec<PathBuf> = files
         .iter()
         .map(path::absolute)
@@ -56,22 +85,53 @@ pub fn import(base_path: &Path, absolute_group_path: &Path, files: &[PathBuf]) -
                 continue;
             }
 
-            // If the file is itself a symlink.
-            if let FileType::Symlink = file_type {
-                println!(
-                    "ERROR: the file you're trying to move {path:?} is a symlink itself, I'm not quite sure if you really meant to move it to the group folder, please handle it manually"
-                );
-            }
-
             // Is file inside of `base_path`? If not, throw error.
             if let Ok(normalized_path) = absolute_path.strip_prefix(base_path) {
-                let to_path = absolute_group_path.join(normalized_path);
+                let mut to_path = absolute_group_path.join(normalized_path);
+
+                if let Some(conditions) = &options.alternate {
+                    let mut file_name = to_path.file_name().unwrap_or_default().to_owned();
+                    file_name.push(ALTERNATE_SEPARATOR);
+                    file_name.push(conditions);
+                    to_path.set_file_name(file_name);
+                }
+
+                let kind = match file_type {
+                    FileType::Symlink if options.dereference => {
+                        let target = fs::canonicalize(path).wrap_err_with(|| {
+                            format!("can't dereference {path:?}, its target doesn't exist")
+                        })?;
+                        println!("Importing the content of {target:?}, the target of {path:?}");
+                        ImportKind::Dereference(target)
+                    }
+                    FileType::Symlink => {
+                        let target = fs::read_link(path)?;
+                        let new_target = rewrite_symlink_target(&target, absolute_path, &to_path);
+                        println!(
+                            "Importing symlink {path:?}, pointing to {new_target:?} from the group \
+                             (was {target:?}), use --dereference to import its target instead"
+                        );
+                        ImportKind::RewriteSymlink(new_target)
+                    }
+                    FileType::Regular | FileType::Directory => ImportKind::Move,
+                };
 
-                let conflict_resolution = check_conflict_resolution(path, &to_path)?;
+                let source = match &kind {
+                    ImportKind::Dereference(target) => target.as_path(),
+                    ImportKind::Move | ImportKind::RewriteSymlink(_) => path,
+                };
+
+                let policy = ConflictPolicy {
+                    interactive: options.interactive,
+                    ..ConflictPolicy::default()
+                };
+                let conflict_resolution =
+                    resolve_conflict(source, &to_path, Operation::Import, &policy)?;
 
                 let file = FileToMove {
                     path,
                     to_path,
+                    kind,
                     conflict_resolution,
                 };
                 files_to_move.push(file);
@@ -129,122 +189,127 @@ pub fn import(base_path: &Path, absolute_group_path: &Path, files: &[PathBuf]) -
     for FileToMove {
         path,
         to_path,
+        kind,
         conflict_resolution,
     } in &files_to_move
     {
-        match conflict_resolution {
-            ImportConflictResolution::None => {}
-            ImportConflictResolution::DeleteRegularFile => {
-                fs::remove_file(to_path)?;
+        if !conflict_resolution.prepare(path, to_path)? {
+            continue;
+        }
+
+        match kind {
+            ImportKind::Move => {
+                cheap_move_with_fallback(path, to_path)
+                    .wrap_err("Failed to move file to import")?;
             }
-            ImportConflictResolution::DeleteDir => {
-                fs::remove_dir(to_path)?;
+            ImportKind::RewriteSymlink(target) => {
+                utils::create_symlink(to_path, target)?;
+                fs::remove_file(path)?;
             }
-            ImportConflictResolution::SkipThis => {
-                continue;
+            ImportKind::Dereference(target) => {
+                utils::expensive_copy(target, to_path)
+                    .wrap_err("Failed to copy symlink target to import")?;
+                fs::remove_file(path)?;
             }
         }
-        cheap_move_with_fallback(path, to_path).wrap_err("Failed to move file to import")?;
     }
 
     Ok(())
 }
 
-fn check_conflict_resolution(from: &Path, to: &Path) -> Result<ImportConflictResolution> {
-    if !try_exists(to)? {
-        return Ok(ImportConflictResolution::None);
-    }
-
-    let (type_from, type_to) = (read_file_type(from)?, read_file_type(to)?);
-
-    use FileType::*;
-    let conflict_resolution = match (type_from, type_to) {
-        (_, Regular) if fs::symlink_metadata(to)?.len() == 0 => {
-            ImportConflictResolution::DeleteRegularFile
-        }
-        (_, Directory) if fs::read_dir(to)?.next().is_none() => ImportConflictResolution::DeleteDir,
-        (Regular, Regular) => {
-            ensure_files_match_content(from, to)?;
-            ImportConflictResolution::SkipThis
+/// Expands glob patterns in `files`, then replaces directories with excluded descendants by
+/// their children that aren't excluded, so the excluded ones stay in place.
+fn expand_file_arguments(
+    base_path: &Path,
+    files: &[PathBuf],
+    exclude: &[Pattern],
+) -> Result<Vec<PathBuf>> {
+    let mut expanded = vec![];
+
+    for file in files {
+        let pattern = file.to_string_lossy();
+
+        // literal paths are kept, even if they contain glob characters
+        if try_exists(file)? || Pattern::escape(&pattern) == pattern {
+            expanded.push(file.clone());
+            continue;
         }
-        (Directory, Directory) => {
-            return Err(eyre!(
-                "can't import {:?}, there is a non-empty directory at {:?}",
-                from,
-                to,
-            ));
-        }
-        (Symlink, Symlink) => {
-            ensure_symlinks_match_target(from, to)?;
-            ImportConflictResolution::SkipThis
-        }
-        (Regular, Directory)
-        | (Regular, Symlink)
-        | (Directory, Regular)
-        | (Directory, Symlink)
-        | (Symlink, Directory)
-        | (Symlink, Regular) => {
-            return Err(eyre!(
-                "can't import {:?}, it conflicts with {:?}, and their types \
-                are different",
-                from,
-                to,
-            ));
-        }
-    };
-
-    Ok(conflict_resolution)
-}
 
-fn ensure_files_match_content(from_path: &Path, to_path: &Path) -> Result<()> {
-    let from = fs::File::open(from_path)?;
-    let to = fs::File::open(to_path)?;
+        let matches = glob::glob(&pattern)
+            .wrap_err_with(|| format!("invalid glob pattern {pattern:?}"))?
+            .collect::<Result<Vec<_>, _>>()?;
+        if matches.is_empty() {
+            bail!("glob pattern {pattern:?} didn't match any file");
+        }
+        expanded.extend(matches);
+    }
 
-    let from_len = from.metadata()?.len();
-    let to_len = to.metadata()?.len();
+    if exclude.is_empty() {
+        return Ok(expanded);
+    }
 
-    fn content_match(a: impl Read, b: impl Read) -> io::Result<bool> {
-        let mut a = BufReader::new(a);
-        let mut b = BufReader::new(b);
+    let absolute_base_path = path::absolute(base_path)?;
+    let mut files_to_import = vec![];
+
+    for file in &expanded {
+        let is_excluded = |path: &Path| {
+            let relative_to_argument = path.strip_prefix(file).unwrap_or(path);
+            let relative_to_base = path::absolute(path).ok().and_then(|path| {
+                path.strip_prefix(&absolute_base_path)
+                    .ok()
+                    .map(Path::to_owned)
+            });
+
+            exclude.iter().any(|pattern| {
+                (!relative_to_argument.as_os_str().is_empty()
+                    && pattern.matches_path(relative_to_argument))
+                    || relative_to_base
+                        .as_ref()
+                        .is_some_and(|path| pattern.matches_path(path))
+            })
+        };
+        split_excluded(file, &is_excluded, &mut files_to_import)?;
+    }
 
-        loop {
-            let slice_a = a.fill_buf()?;
-            let slice_b = b.fill_buf()?;
-            let len_a = slice_a.len();
-            let len_b = slice_b.len();
+    Ok(files_to_import)
+}
 
-            if len_a == 0 || len_b == 0 {
-                assert_eq!(len_a, len_b, "should check len before, or arithmetic bug");
-                return Ok(true);
-            }
+/// Pushes `path` into `files`, or, if it's a directory with excluded descendants, its children
+/// that aren't excluded (splitting them too).
+fn split_excluded(
+    path: &Path,
+    is_excluded: &impl Fn(&Path) -> bool,
+    files: &mut Vec<PathBuf>,
+) -> Result<()> {
+    if is_excluded(path) {
+        println!("Excluding {path:?}");
+        return Ok(());
+    }
 
-            let min = len_a.min(len_b);
+    if read_file_type(path)? != FileType::Directory {
+        files.push(path.to_owned());
+        return Ok(());
+    }
 
-            if slice_a[..min] != slice_b[..min] {
-                return Ok(false);
-            }
+    let tree = FsTree::symlink_read_at(path)?;
+    let has_excluded_descendant = tree
+        .paths()
+        .any(|relative_path| is_excluded(&path.join(relative_path)));
 
-            a.consume(min);
-            b.consume(min);
-        }
+    if !has_excluded_descendant {
+        files.push(path.to_owned());
+        return Ok(());
     }
 
-    if from_len != to_len || !content_match(from, to)? {
-        return Err(eyre!(
-            "can't import {from_path:?}, it conflicts with {to_path:?}, and their content is different",
-        ));
-    }
-    Ok(())
-}
+    let mut children = fs::read_dir(path)?
+        .map(|entry| Ok(entry?.path()))
+        .collect::<io::Result<Vec<_>>>()?;
+    children.sort();
 
-fn ensure_symlinks_match_target(from_path: &Path, to_path: &Path) -> Result<()> {
-    assert_eq!(FileType::Symlink, read_file_type(from_path)?);
-    assert_eq!(FileType::Symlink, read_file_type(to_path)?);
-    if fs::read_link(from_path)? != fs::read_link(to_path)? {
-        return Err(eyre!(
-            "can't import {from_path:?}, it conflicts with {to_path:?}, they're both symlinks but their targets are different",
-        ));
+    for child in children {
+        split_excluded(&child, is_excluded, files)?;
     }
+
     Ok(())
 }
 
@@ -256,7 +321,11 @@ mod tests {
     use pretty_assertions::assert_eq;
 
     use super::*;
-    use crate::{commands::link::link, utils::test_utils::cd_to_testdir};
+    use crate::{
+        commands::link::{LinkOptions, link},
+        state::GroupState,
+        utils::test_utils::cd_to_testdir,
+    };
 
     #[test]
     fn test_import() {
@@ -348,6 +417,7 @@ mod tests {
             test_dir,
             &test_dir.join("dotfiles/group_name"),
             &files_to_import,
+            &ImportOptions::default(),
         )
         .unwrap();
 
@@ -396,6 +466,7 @@ mod tests {
             &base_dir,
             &test_dir.join("dotfiles/sddm"),
             ["base/etc/config"].map(PathBuf::from).as_slice(),
+            &ImportOptions::default(),
         )
         .unwrap();
 
@@ -419,10 +490,11 @@ mod tests {
         };
 
         let expected_home = tree! {};
+        // rewritten to still point to the same place
         let expected_dotfiles = tree! {
             dotfiles: [
                 group: [
-                    link -> any_target
+                    link -> "../../any_target"
                 ]
             ]
         };
@@ -434,6 +506,7 @@ mod tests {
             test_dir,
             &test_dir.join("dotfiles/group"),
             ["link"].map(PathBuf::from).as_slice(),
+            &ImportOptions::default(),
         )
         .unwrap();
 
@@ -469,6 +542,7 @@ mod tests {
             test_dir,
             &test_dir.join("dotfiles/group"),
             ["file"].map(PathBuf::from).as_slice(),
+            &ImportOptions::default(),
         )
         .unwrap_err()
         .to_string();
@@ -511,6 +585,7 @@ mod tests {
             test_dir,
             &test_dir.join("dotfiles/group"),
             ["file"].map(PathBuf::from).as_slice(),
+            &ImportOptions::default(),
         )
         .unwrap();
 
@@ -546,6 +621,7 @@ mod tests {
             test_dir,
             &test_dir.join("dotfiles/group"),
             ["dir"].map(PathBuf::from).as_slice(),
+            &ImportOptions::default(),
         )
         .unwrap_err()
         .to_string();
@@ -578,6 +654,7 @@ mod tests {
             test_dir,
             &test_dir.join("dotfiles/group"),
             ["link"].map(PathBuf::from).as_slice(),
+            &ImportOptions::default(),
         )
         .unwrap_err()
         .to_string();
@@ -602,7 +679,7 @@ mod tests {
         let dotfiles = tree! {
             dotfiles: [
                 group: [
-                    link -> target
+                    link -> "../../target"
                 ]
             ]
         };
@@ -628,6 +705,7 @@ mod tests {
             test_dir,
             &test_dir.join("dotfiles/group"),
             ["link"].map(PathBuf::from).as_slice(),
+            &ImportOptions::default(),
         )
         .unwrap();
 
@@ -648,8 +726,9 @@ mod tests {
             tree! {
                 name
             },
+            // absolute, so importing doesn't rewrite it
             tree! {
-                name -> target
+                name -> "/target"
             },
             tree! {
                 name: [
@@ -690,6 +769,7 @@ mod tests {
                 test_dir,
                 &test_dir.join("dotfiles/group"),
                 ["name"].map(PathBuf::from).as_slice(),
+                &ImportOptions::default(),
             )
             .unwrap();
 
@@ -731,6 +811,7 @@ mod tests {
                 test_dir,
                 &test_dir.join("dotfiles/group"),
                 ["name"].map(PathBuf::from).as_slice(),
+                &ImportOptions::default(),
             )
             .unwrap();
 
@@ -784,14 +865,198 @@ mod tests {
             test_dir,
             &test_dir.join("dotfiles/mygroup"),
             &[".config/my_app/config"].map(PathBuf::from),
+            &ImportOptions::default(),
         )
         .unwrap();
 
-        link(test_dir, &test_dir.join("dotfiles/mygroup")).unwrap();
+        link(
+            test_dir,
+            &test_dir.join("dotfiles/mygroup"),
+            &LinkOptions::default(),
+            &mut GroupState::default(),
+        )
+        .unwrap();
 
         let home_result = expected_home.symlink_read_structure_at(".").unwrap();
         assert_eq!(home_result, expected_home);
         let dotfiles_result = expected_dotfiles.symlink_read_structure_at(".").unwrap();
         assert_eq!(dotfiles_result, expected_dotfiles);
     }
+
+    #[test]
+    fn test_import_as_alternate() {
+        let (_dropper, test_dir) = cd_to_testdir().unwrap();
+
+        let home = tree! {
+            ".Xresources"
+        };
+        let dotfiles = tree! {
+            dotfiles: [
+                x11: [
+                    ".Xresources##default"
+                ]
+            ]
+        };
+
+        let expected_home = tree! {};
+        let expected_dotfiles = tree! {
+            dotfiles: [
+                x11: [
+                    ".Xresources##default"
+                    ".Xresources##host.desk"
+                ]
+            ]
+        };
+
+        home.write_structure_at(".").unwrap();
+        dotfiles.write_structure_at(".").unwrap();
+
+        let options = ImportOptions {
+            alternate: Some("host.desk".to_owned()),
+            ..ImportOptions::default()
+        };
+        import(
+            test_dir,
+            &test_dir.join("dotfiles/x11"),
+            &[".Xresources"].map(PathBuf::from),
+            &options,
+        )
+        .unwrap();
+
+        let home_result = expected_home.symlink_read_structure_at(".").unwrap();
+        assert_eq!(home_result, expected_home);
+        let dotfiles_result = expected_dotfiles.symlink_read_structure_at(".").unwrap();
+        assert_eq!(dotfiles_result, expected_dotfiles);
+    }
+
+    #[test]
+    fn test_import_with_globs_and_excludes() {
+        let (_dropper, test_dir) = cd_to_testdir().unwrap();
+
+        let home = tree! {
+            ".config": [
+                nvim: [
+                    "init.lua"
+                    "debug.log"
+                    plugin: [
+                        "packer_compiled.lua"
+                        "other.lua"
+                    ]
+                    lua: [
+                        "plugins.lua"
+                    ]
+                ]
+                "a.conf"
+                "b.conf"
+                "c.toml"
+            ]
+        };
+        let dotfiles = tree! {
+            dotfiles: [
+                group: []
+            ]
+        };
+
+        home.write_structure_at(".").unwrap();
+        dotfiles.write_structure_at(".").unwrap();
+
+        let options = ImportOptions {
+            exclude: ["plugin/packer_compiled.lua", "*.log"]
+                .map(|pattern| Pattern::new(pattern).unwrap())
+                .into(),
+            ..ImportOptions::default()
+        };
+        let files = [".config/nvim", ".config/*.conf"].map(PathBuf::from);
+
+        import(test_dir, &test_dir.join("dotfiles/group"), &files, &options).unwrap();
+
+        let expected_home = tree! {
+            ".config": [
+                nvim: [
+                    "debug.log"
+                    plugin: [
+                        "packer_compiled.lua"
+                    ]
+                ]
+                "c.toml"
+            ]
+        };
+        let expected_dotfiles = tree! {
+            dotfiles: [
+                group: [
+                    ".config": [
+                        nvim: [
+                            "init.lua"
+                            plugin: [
+                                "other.lua"
+                            ]
+                            lua: [
+                                "plugins.lua"
+                            ]
+                        ]
+                        "a.conf"
+                        "b.conf"
+                    ]
+                ]
+            ]
+        };
+
+        let home_result = FsTree::symlink_read_at(".config").unwrap();
+        assert_eq!(
+            home_result,
+            expected_home.children().unwrap()[Path::new(".config")]
+        );
+        let dotfiles_result = FsTree::symlink_read_at("dotfiles").unwrap();
+        assert_eq!(
+            dotfiles_result,
+            expected_dotfiles.children().unwrap()[Path::new("dotfiles")]
+        );
+    }
+
+    #[test]
+    fn test_import_symlink_dereferenced() {
+        let (_dropper, test_dir) = cd_to_testdir().unwrap();
+
+        let home = tree! {
+            ".bashrc" -> "data/bashrc"
+            data: [
+                bashrc
+            ]
+        };
+        let dotfiles = tree! {
+            dotfiles: [
+                group: []
+            ]
+        };
+
+        home.write_structure_at(".").unwrap();
+        dotfiles.write_structure_at(".").unwrap();
+        fs::write("data/bashrc", "content").unwrap();
+
+        let options = ImportOptions {
+            dereference: true,
+            ..ImportOptions::default()
+        };
+        import(
+            test_dir,
+            &test_dir.join("dotfiles/group"),
+            &[".bashrc"].map(PathBuf::from),
+            &options,
+        )
+        .unwrap();
+
+        let expected = tree! {
+            data: [
+                bashrc
+            ]
+            dotfiles: [
+                group: [
+                    ".bashrc"
+                ]
+            ]
+        };
+        assert_eq!(FsTree::symlink_read_at(".").unwrap(), expected);
+        let content = fs::read_to_string("dotfiles/group/.bashrc").unwrap();
+        assert_eq!(content, "content");
+    }
 }
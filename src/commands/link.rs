@@ -1,25 +1,61 @@
 use std::path::Path;
 
-use eyre::WrapErr;
+use eyre::{WrapErr, bail};
 use fs_err as fs;
 use fs_tree::FsTree;
 
 use crate::{
     Result,
-    utils::{self, create_relative_symlink_target_path},
+    alternate::{choose_alternates, plain_path_of_alternate},
+    permissions::Permissions,
+    state::GroupState,
+    template::{TemplateVariables, render_file, template_output_path},
+    utils::{
+        self, FileType, are_equivalent_symlink_targets, cheap_move_with_fallback,
+        create_relative_symlink_target_path, files_match_content, read_file_type, read_node,
+        try_exists,
+    },
 };
 
-pub fn link(base_dir: &Path, group_dir: &Path) -> Result<()> {
+#[derive(Debug, Default, Clone)]
+pub struct LinkOptions {
+    /// Move conflicting files from the base folder into the group, then link them.
+    pub adopt: bool,
+    /// Let `adopt` take directories that aren't empty, and replace rendered templates that were
+    /// edited since.
+    pub force: bool,
+    /// Rename conflicting files with this backup suffix before linking.
+    pub backup: Option<String>,
+    /// Variables used to render templates.
+    pub template_variables: TemplateVariables,
+    /// Modes declared for the group files, applied before linking.
+    pub permissions: Permissions,
+}
+
+/// Links the group into the base folder, recording what was created into `state`.
+pub fn link(
+    base_dir: &Path,
+    group_dir: &Path,
+    options: &LinkOptions,
+    state: &mut GroupState,
+) -> Result<()> {
+    utils::remove_leftover_temporaries(group_dir)?;
+    options.permissions.apply(group_dir)?;
+
     let group_tree = FsTree::symlink_read_at(group_dir).wrap_err("reading dotfiles folder tree")?;
 
-    let base_tree = group_tree
-        .symlink_read_structure_at(base_dir)
-        .wrap_err("reading structured file tree at base folder")?;
+    let alternates = choose_alternates(&group_tree, &options.template_variables)?;
+
+    for (plain_path, chosen) in &alternates {
+        if chosen.is_none() {
+            println!("No alternate of {plain_path:?} matches this machine, skipping it");
+        }
+    }
 
     let mut intermediate_directories_linked = vec![];
 
     for (group_node, relative_path) in &group_tree {
-        // Skip children where the parent directory is already linked
+        // Skip children where the parent directory is already linked (or can't be created)
         if intermediate_directories_linked
             .iter()
             .any(|intermediate_dir| relative_path.starts_with(intermediate_dir))
@@ -27,24 +63,89 @@ pub fn link(base_dir: &Path, group_dir: &Path) -> Result<()> {
             continue;
         }
 
-        let base_absolute = base_dir.join(&relative_path);
         let dotfile_absolute = group_dir.join(&relative_path);
+
+        // alternates are linked as a whole, at their plain path
+        let (base_absolute, is_leaf) =
+            if let Some((plain_path, _)) = plain_path_of_alternate(&relative_path) {
+                intermediate_directories_linked.push(relative_path.clone());
+
+                let chosen = alternates.get(&plain_path).and_then(Option::as_ref);
+                if chosen != Some(&relative_path) {
+                    continue;
+                }
+
+                (base_dir.join(&plain_path), true)
+            } else {
+                (
+                    utils::join_relative(base_dir, &relative_path),
+                    group_node.is_leaf(),
+                )
+            };
+
+        // read as the loop goes, so parents backed up and recreated are seen by their children
+        let base_node = read_node(&base_absolute)?;
+        let base_node = base_node.as_ref();
+
+        // templates are rendered instead of linked
+        if group_node.is_regular()
+            && let Some(output_relative_path) = template_output_path(&relative_path)
+        {
+            render_template(
+                &dotfile_absolute,
+                &base_dir.join(output_relative_path),
+                options,
+                state,
+            )?;
+            continue;
+        }
+
         let symlink_target = create_relative_symlink_target_path(&base_absolute, &dotfile_absolute);
 
         // if already exists at base folder
-        if let Some(base_node) = base_tree.get(&relative_path) {
-            if group_node.is_leaf() {
+        if let Some(base_node) = base_node {
+            if is_leaf {
                 if let Some(current_target) = base_node.target() {
-                    if current_target == symlink_target {
+                    if are_equivalent_symlink_targets(
+                        &base_absolute,
+                        current_target,
+                        &symlink_target,
+                    ) {
                         println!("OK: skipping link {base_absolute:?}");
                         if group_node.is_dir() {
                             intermediate_directories_linked.push(relative_path);
                         }
+                    } else if let Some(suffix) = &options.backup {
+                        backup(&base_absolute, suffix)?;
+                        utils::create_symlink(&base_absolute, &symlink_target)?;
+                        state.symlinks.insert(base_absolute.clone());
                     } else {
                         println!(
                             "ERROR: {base_absolute:?} exists but points to {current_target:?} instead of {symlink_target:?}"
                         );
                     }
+                } else if group_node.is_regular()
+                    && base_node.is_regular()
+                    && files_match_content(&base_absolute, &dotfile_absolute)?
+                {
+                    fs::remove_file(&base_absolute)?;
+                    utils::create_symlink(&base_absolute, &symlink_target)?;
+                    state.symlinks.insert(base_absolute.clone());
+                    println!(
+                        "Resolved: {base_absolute:?} had the same content as the group file, replaced it by a link"
+                    );
+                } else if options.adopt {
+                    adopt(
+                        &base_absolute,
+                        &dotfile_absolute,
+                        &symlink_target,
+                        options.force,
+                        state,
+                    )?;
+                } else if let Some(suffix) = &options.backup {
+                    backup(&base_absolute, suffix)?;
+                    utils::create_symlink(&base_absolute, &symlink_target)?;
+                    state.symlinks.insert(base_absolute.clone());
                 } else {
                     println!(
                         "ERROR: can't create link at {base_absolute:?} because a {} already exists",
@@ -52,18 +153,31 @@ pub fn link(base_dir: &Path, group_dir: &Path) -> Result<()> {
                     );
                 }
             } else if base_node.is_dir() {
-                // great! directory found where non-leaf was expected, no need to create one
+                // great! directory found where non-leaf was expected, no need to create one,
+                // but clean up what interrupted moves into it left behind
+                utils::remove_leftover_temporaries_at(&base_absolute)?;
+            } else if let Some(suffix) = &options.backup {
+                backup(&base_absolute, suffix)?;
+                fs::create_dir(&base_absolute).wrap_err("creating directory for dotfile")?;
+                state.directories.insert(base_absolute.clone());
+                println!("Created intermediate directory at {base_absolute:?}");
             } else {
-                println!("ERROR: can't create link at {base_absolute:?} because it's a directory");
+                println!(
+                    "ERROR: can't create directory at {base_absolute:?} because a {} already exists",
+                    base_node.variant_str(),
+                );
+                intermediate_directories_linked.push(relative_path);
             }
         } else {
             // only link the leaves, non-leafs are created like `mkdir`
             // (note: a non-leaf is a dir, but a dir can be a leaf)
-            if group_node.is_leaf() {
+            if is_leaf {
                 utils::create_symlink(&base_absolute, &symlink_target)?;
+                state.symlinks.insert(base_absolute.clone());
                 println!("Linked {} at {relative_path:?}", group_node.variant_str());
             } else {
                 fs::create_dir(&base_absolute).wrap_err("creating directory for dotfile")?;
+                state.directories.insert(base_absolute.clone());
                 println!("Created intermediate directory at {base_absolute:?}");
             }
         }
@@ -72,6 +186,116 @@ pub fn link(base_dir: &Path, group_dir: &Path) -> Result<()> {
     Ok(())
 }
 
+/// Moves the file at `base_absolute` into the group, replacing `dotfile_absolute`, and links it
+/// back.
+fn adopt(
+    base_absolute: &Path,
+    dotfile_absolute: &Path,
+    symlink_target: &Path,
+    force: bool,
+    state: &mut GroupState,
+) -> Result<()> {
+    let base_type = read_file_type(base_absolute)?;
+
+    if base_type == FileType::Directory && !force && fs::read_dir(base_absolute)?.next().is_some() {
+        println!(
+            "ERROR: refusing to adopt non-empty directory at {base_absolute:?}, use --force to adopt it anyway"
+        );
+        return Ok(());
+    }
+
+    // `dotfile_absolute` is a leaf, so if it's a directory, it's empty
+    match read_file_type(dotfile_absolute)? {
+        FileType::Directory => fs::remove_dir(dotfile_absolute)?,
+        FileType::Regular | FileType::Symlink => fs::remove_file(dotfile_absolute)?,
+    }
+
+    cheap_move_with_fallback(base_absolute, dotfile_absolute)
+        .wrap_err("Failed to move file into group")?;
+    utils::create_symlink(base_absolute, symlink_target)?;
+    state.symlinks.insert(base_absolute.to_owned());
+    println!("Adopted {base_type} at {base_absolute:?} into the group");
+
+    Ok(())
+}
+
+/// Writes the rendered template to `output_path`, replacing the output of a previous render, if
+/// it wasn't edited since (or with `force`).
+///
+/// Other files in the way are conflicts, like for links, they're only replaced with `backup`,
+/// `adopt` can't take them since they can't be moved into the group in place of the template.
+fn render_template(
+    template_path: &Path,
+    output_path: &Path,
+    options: &LinkOptions,
+    state: &mut GroupState,
+) -> Result<()> {
+    let rendered = render_file(template_path, &options.template_variables)?;
+
+    if try_exists(output_path)? {
+        let file_type = read_file_type(output_path)?;
+
+        let is_regular = file_type == FileType::Regular;
+        let content = if is_regular {
+            fs::read(output_path)?
+        } else {
+            vec![]
+        };
+
+        if is_regular && content == rendered.as_bytes() {
+            println!("OK: skipping rendered template {output_path:?}");
+            state.record_render(output_path, &content);
+            return Ok(());
+        }
+
+        let is_recorded = is_regular && state.rendered.contains_key(output_path);
+        let is_unchanged_render = is_recorded && state.is_last_render(output_path, &content);
+        let can_replace = is_unchanged_render || (is_recorded && options.force);
+
+        if !can_replace {
+            if let Some(suffix) = &options.backup {
+                backup(output_path, suffix)?;
+            } else if is_recorded {
+                println!(
+                    "ERROR: {output_path:?} was edited since it was rendered, use --backup or --force to replace it"
+                );
+                return Ok(());
+            } else {
+                println!(
+                    "ERROR: can't render template at {output_path:?} because a {file_type} already exists"
+                );
+                return Ok(());
+            }
+        }
+    }
+
+    fs::write(output_path, &rendered)?;
+    fs::set_permissions(
+        output_path,
+        fs::symlink_metadata(template_path)?.permissions(),
+    )?;
+    state.record_render(output_path, rendered.as_bytes());
+    println!("Rendered template at {output_path:?}");
+
+    Ok(())
+}
+
+/// Renames the file at `base_absolute` aside, so a link can take its place.
+fn backup(base_absolute: &Path, suffix: &str) -> Result<()> {
+    let backup_path = utils::backup_path(base_absolute, suffix);
+
+    if try_exists(&backup_path)? {
+        bail!("can't back up {base_absolute:?}, {backup_path:?} already exists");
+    }
+
+    fs::rename(base_absolute, &backup_path)?;
+    println!(
+        "Backed up {base_absolute:?} to {backup_path:?}, run `dotin unlink --restore-backups` to restore it"
+    );
+
+    Ok(())
+}
+
 #[cfg(test)]
 mod tests {
     use fs_tree::tree;
@@ -112,7 +336,13 @@ mod tests {
         home.write_structure_at(".").unwrap();
         dotfiles.write_structure_at(".").unwrap();
 
-        link(test_dir, &test_dir.join("dotfiles/i3")).unwrap();
+        link(
+            test_dir,
+            &test_dir.join("dotfiles/i3"),
+            &LinkOptions::default(),
+            &mut GroupState::default(),
+        )
+        .unwrap();
 
         let result = expected_home.symlink_read_structure_at(".").unwrap();
         assert_eq!(result, expected_home);
@@ -146,9 +376,457 @@ mod tests {
         base.write_structure_at(".").unwrap();
         dotfiles.write_structure_at(".").unwrap();
 
-        link(&base_dir, &test_dir.join("dotfiles/sddm")).unwrap();
+        link(
+            &base_dir,
+            &test_dir.join("dotfiles/sddm"),
+            &LinkOptions::default(),
+            &mut GroupState::default(),
+        )
+        .unwrap();
 
         let result = expected_base.symlink_read_structure_at(".").unwrap();
         assert_eq!(result, expected_base);
     }
+
+    #[test]
+    fn test_link_adopt_regular_file() {
+        let (_dropper, test_dir) = cd_to_testdir().unwrap();
+
+        let home = tree! {
+            ".bashrc"
+        };
+        let dotfiles = tree! {
+            dotfiles: [
+                bash: [
+                    ".bashrc"
+                ]
+            ]
+        };
+        let expected_home = tree! {
+            ".bashrc" -> "dotfiles/bash/.bashrc"
+        };
+
+        home.write_structure_at(".").unwrap();
+        dotfiles.write_structure_at(".").unwrap();
+        fs::write(test_dir.join(".bashrc"), "from home").unwrap();
+        fs::write(test_dir.join("dotfiles/bash/.bashrc"), "from group").unwrap();
+
+        let options = LinkOptions {
+            adopt: true,
+            ..LinkOptions::default()
+        };
+        link(
+            test_dir,
+            &test_dir.join("dotfiles/bash"),
+            &options,
+            &mut GroupState::default(),
+        )
+        .unwrap();
+
+        let result = expected_home.symlink_read_structure_at(".").unwrap();
+        assert_eq!(result, expected_home);
+        let content = fs::read_to_string(test_dir.join("dotfiles/bash/.bashrc")).unwrap();
+        assert_eq!(content, "from home");
+    }
+
+    #[test]
+    fn test_link_adopt_non_empty_dir_requires_force() {
+        let (_dropper, test_dir) = cd_to_testdir().unwrap();
+
+        let home = tree! {
+            nvim: [
+                "init.lua"
+            ]
+        };
+        let dotfiles = tree! {
+            dotfiles: [
+                nvim: [
+                    nvim: []
+                ]
+            ]
+        };
+        let expected_home_forced = tree! {
+            nvim -> "dotfiles/nvim/nvim"
+        };
+        let expected_dotfiles_forced = tree! {
+            dotfiles: [
+                nvim: [
+                    nvim: [
+                        "init.lua"
+                    ]
+                ]
+            ]
+        };
+
+        home.write_structure_at(".").unwrap();
+        dotfiles.write_structure_at(".").unwrap();
+
+        let mut options = LinkOptions {
+            adopt: true,
+            ..LinkOptions::default()
+        };
+        link(
+            test_dir,
+            &test_dir.join("dotfiles/nvim"),
+            &options,
+            &mut GroupState::default(),
+        )
+        .unwrap();
+
+        // Nothing was adopted
+        let home_result = home.symlink_read_structure_at(".").unwrap();
+        assert_eq!(home_result, home);
+        let dotfiles_result = dotfiles.symlink_read_structure_at(".").unwrap();
+        assert_eq!(dotfiles_result, dotfiles);
+
+        options.force = true;
+        link(
+            test_dir,
+            &test_dir.join("dotfiles/nvim"),
+            &options,
+            &mut GroupState::default(),
+        )
+        .unwrap();
+
+        let home_result = expected_home_forced.symlink_read_structure_at(".").unwrap();
+        assert_eq!(home_result, expected_home_forced);
+        let dotfiles_result = expected_dotfiles_forced
+            .symlink_read_structure_at(".")
+            .unwrap();
+        assert_eq!(dotfiles_result, expected_dotfiles_forced);
+    }
+
+    #[test]
+    fn test_link_backup() {
+        let (_dropper, test_dir) = cd_to_testdir().unwrap();
+
+        let home = tree! {
+            ".bashrc"
+            ".profile" -> elsewhere
+        };
+        let dotfiles = tree! {
+            dotfiles: [
+                shell: [
+                    ".bashrc"
+                    ".profile"
+                ]
+            ]
+        };
+        let expected_home = tree! {
+            ".bashrc" -> "dotfiles/shell/.bashrc"
+            ".bashrc.dotin-backup-old"
+            ".profile" -> "dotfiles/shell/.profile"
+            ".profile.dotin-backup-old" -> elsewhere
+        };
+
+        home.write_structure_at(".").unwrap();
+        dotfiles.write_structure_at(".").unwrap();
+        fs::write(test_dir.join(".bashrc"), "from home").unwrap();
+
+        let options = LinkOptions {
+            backup: Some("old".into()),
+            ..LinkOptions::default()
+        };
+        link(
+            test_dir,
+            &test_dir.join("dotfiles/shell"),
+            &options,
+            &mut GroupState::default(),
+        )
+        .unwrap();
+
+        let result = expected_home.symlink_read_structure_at(".").unwrap();
+        assert_eq!(result, expected_home);
+    }
+
+    #[test]
+    fn test_link_removes_leftover_temporaries() {
+        let (_dropper, test_dir) = cd_to_testdir().unwrap();
+
+        let dotfiles = tree! {
+            dotfiles: [
+                i3: [
+                    ".config": [
+                        i3: [
+                            config
+                        ]
+                    ]
+                ]
+            ]
+        };
+        dotfiles.write_structure_at(".").unwrap();
+
+        // PIDs never get this high, so this process isn't running
+        let leftover = format!("{}4294967295-config", utils::TEMPORARY_PREFIX);
+        fs::create_dir(test_dir.join(".config")).unwrap();
+        fs::write(test_dir.join(".config").join(&leftover), "").unwrap();
+        fs::write(test_dir.join("dotfiles/i3").join(&leftover), "").unwrap();
+
+        link(
+            test_dir,
+            &test_dir.join("dotfiles/i3"),
+            &LinkOptions::default(),
+            &mut GroupState::default(),
+        )
+        .unwrap();
+
+        assert!(!try_exists(test_dir.join(".config").join(&leftover)).unwrap());
+        assert!(!try_exists(test_dir.join("dotfiles/i3").join(&leftover)).unwrap());
+        assert!(test_dir.join(".config/i3/config").is_symlink());
+    }
+
+    #[test]
+    fn test_link_backup_in_place_of_directory() {
+        let (_dropper, test_dir) = cd_to_testdir().unwrap();
+
+        let home = tree! {
+            ".config"
+            scripts -> elsewhere
+        };
+        let dotfiles = tree! {
+            dotfiles: [
+                shell: [
+                    ".config": [
+                        starship
+                    ]
+                    scripts: [
+                        "run.sh"
+                    ]
+                ]
+            ]
+        };
+        let expected_home = tree! {
+            ".config": [
+                starship -> "../dotfiles/shell/.config/starship"
+            ]
+            ".config.dotin-backup-old"
+            scripts: [
+                "run.sh" -> "../dotfiles/shell/scripts/run.sh"
+            ]
+            "scripts.dotin-backup-old" -> elsewhere
+        };
+
+        home.write_structure_at(".").unwrap();
+        dotfiles.write_structure_at(".").unwrap();
+
+        let options = LinkOptions {
+            backup: Some("old".into()),
+            ..LinkOptions::default()
+        };
+        let mut state = GroupState::default();
+        link(
+            test_dir,
+            &test_dir.join("dotfiles/shell"),
+            &options,
+            &mut state,
+        )
+        .unwrap();
+
+        let result = expected_home.symlink_read_structure_at(".").unwrap();
+        assert_eq!(result, expected_home);
+        assert_eq!(state.directories.len(), 2);
+    }
+
+    #[test]
+    fn test_link_replaces_identical_file() {
+        let (_dropper, test_dir) = cd_to_testdir().unwrap();
+
+        let home = tree! {
+            same
+            different
+        };
+        let dotfiles = tree! {
+            dotfiles: [
+                group: [
+                    same
+                    different
+                ]
+            ]
+        };
+        let expected_home = tree! {
+            same -> "dotfiles/group/same"
+            different
+        };
+
+        home.write_structure_at(".").unwrap();
+        dotfiles.write_structure_at(".").unwrap();
+        fs::write(test_dir.join("same"), "aaa").unwrap();
+        fs::write(test_dir.join("dotfiles/group/same"), "aaa").unwrap();
+        fs::write(test_dir.join("different"), "aaa").unwrap();
+        fs::write(test_dir.join("dotfiles/group/different"), "bbb").unwrap();
+
+        link(
+            test_dir,
+            &test_dir.join("dotfiles/group"),
+            &LinkOptions::default(),
+            &mut GroupState::default(),
+        )
+        .unwrap();
+
+        let result = expected_home.symlink_read_structure_at(".").unwrap();
+        assert_eq!(result, expected_home);
+    }
+
+    #[test]
+    fn test_link_renders_templates() {
+        let (_dropper, test_dir) = cd_to_testdir().unwrap();
+
+        let home = tree! {
+            ".gitconfig"
+        };
+        let dotfiles = tree! {
+            dotfiles: [
+                git: [
+                    ".gitconfig.dotin.tmpl"
+                ]
+            ]
+        };
+
+        home.write_structure_at(".").unwrap();
+        dotfiles.write_structure_at(".").unwrap();
+        fs::write(
+            test_dir.join("dotfiles/git/.gitconfig.dotin.tmpl"),
+            "email = {{ email }}\n",
+        )
+        .unwrap();
+        fs::write(test_dir.join(".gitconfig"), "email = stale\n").unwrap();
+
+        let mut options = LinkOptions::default();
+        options
+            .template_variables
+            .inner
+            .insert("email".into(), "me@example.com".into());
+        let mut state = GroupState::default();
+        let group_dir = test_dir.join("dotfiles/git");
+
+        // a file that wasn't rendered by `link` is a conflict
+        link(test_dir, &group_dir, &options, &mut state).unwrap();
+        let content = fs::read_to_string(test_dir.join(".gitconfig")).unwrap();
+        assert_eq!(content, "email = stale\n");
+        assert!(state.rendered.is_empty());
+
+        let backup_options = LinkOptions {
+            backup: Some("old".to_owned()),
+            ..options.clone()
+        };
+        link(test_dir, &group_dir, &backup_options, &mut state).unwrap();
+        let rendered = fs::read_to_string(test_dir.join(".gitconfig")).unwrap();
+        assert_eq!(rendered, "email = me@example.com\n");
+        let backup = fs::read_to_string(test_dir.join(".gitconfig.dotin-backup-old")).unwrap();
+        assert_eq!(backup, "email = stale\n");
+        assert!(!test_dir.join(".gitconfig.dotin.tmpl").exists());
+
+        // previous renders are replaced
+        options
+            .template_variables
+            .inner
+            .insert("email".into(), "new@example.com".into());
+        link(test_dir, &group_dir, &options, &mut state).unwrap();
+        let rendered = fs::read_to_string(test_dir.join(".gitconfig")).unwrap();
+        assert_eq!(rendered, "email = new@example.com\n");
+        assert!(state.rendered.contains_key(&test_dir.join(".gitconfig")));
+
+        // edited renders are only replaced with --force (or backed up)
+        fs::write(test_dir.join(".gitconfig"), "email = edited\n").unwrap();
+        options
+            .template_variables
+            .inner
+            .insert("email".into(), "other@example.com".into());
+        link(test_dir, &group_dir, &options, &mut state).unwrap();
+        let content = fs::read_to_string(test_dir.join(".gitconfig")).unwrap();
+        assert_eq!(content, "email = edited\n");
+
+        let force_options = LinkOptions {
+            force: true,
+            ..options.clone()
+        };
+        link(test_dir, &group_dir, &force_options, &mut state).unwrap();
+        let rendered = fs::read_to_string(test_dir.join(".gitconfig")).unwrap();
+        assert_eq!(rendered, "email = other@example.com\n");
+    }
+
+    #[test]
+    fn test_link_adopt_doesnt_take_template_conflicts() {
+        let (_dropper, test_dir) = cd_to_testdir().unwrap();
+
+        let dotfiles = tree! {
+            dotfiles: [
+                git: [
+                    ".gitconfig.dotin.tmpl"
+                ]
+            ]
+        };
+        dotfiles.write_structure_at(".").unwrap();
+        fs::write(test_dir.join(".gitconfig"), "mine\n").unwrap();
+
+        let options = LinkOptions {
+            adopt: true,
+            ..LinkOptions::default()
+        };
+        let mut state = GroupState::default();
+        link(
+            test_dir,
+            &test_dir.join("dotfiles/git"),
+            &options,
+            &mut state,
+        )
+        .unwrap();
+
+        let expected_home = tree! {
+            ".gitconfig"
+            dotfiles: [
+                git: [
+                    ".gitconfig.dotin.tmpl"
+                ]
+            ]
+        };
+        let result = expected_home.symlink_read_structure_at(".").unwrap();
+        assert_eq!(result, expected_home);
+        let content = fs::read_to_string(test_dir.join(".gitconfig")).unwrap();
+        assert_eq!(content, "mine\n");
+        assert!(state.rendered.is_empty());
+    }
+
+    #[test]
+    fn test_link_alternates() {
+        let (_dropper, test_dir) = cd_to_testdir().unwrap();
+
+        let home = tree! {};
+        let dotfiles = tree! {
+            dotfiles: [
+                x11: [
+                    ".Xresources##default"
+                    ".Xresources##host.desk"
+                    "scripts##host.desk": [
+                        "run.sh"
+                    ]
+                    "only_laptop##host.laptop"
+                ]
+            ]
+        };
+        let expected_home = tree! {
+            ".Xresources" -> "dotfiles/x11/.Xresources##host.desk"
+            scripts -> "dotfiles/x11/scripts##host.desk"
+        };
+
+        home.write_structure_at(".").unwrap();
+        dotfiles.write_structure_at(".").unwrap();
+
+        let mut options = LinkOptions::default();
+        options
+            .template_variables
+            .inner
+            .insert("hostname".into(), "desk".into());
+        link(
+            test_dir,
+            &test_dir.join("dotfiles/x11"),
+            &options,
+            &mut GroupState::default(),
+        )
+        .unwrap();
+
+        let mut result = FsTree::symlink_read_at(".").unwrap();
+        result.children_mut().unwrap().remove(Path::new("dotfiles"));
+        assert_eq!(result, expected_home);
+    }
 }
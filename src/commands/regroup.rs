@@ -0,0 +1,545 @@
+use std::path::{self, Path, PathBuf};
+
+use eyre::{WrapErr, bail};
+use fs_err as fs;
+use fs_tree::FsTree;
+
+use crate::{
+    Result,
+    alternate::plain_path_of_alternate,
+    conflict::{ConflictPolicy, ConflictResolution, Operation, resolve_conflict},
+    utils::{
+        self, FileType, PathTrie, are_equivalent_symlink_targets, cheap_move_with_fallback,
+        create_relative_symlink_target_path, join_relative, read_file_type, try_exists,
+    },
+};
+
+#[derive(Debug)]
+struct PathToMove {
+    from: PathBuf,
+    to: PathBuf,
+    conflict_resolution: ConflictResolution,
+    /// Links at the base folder into `from`, and their targets once moved to `to`.
+    links: Vec<(PathBuf, PathBuf)>,
+}
+
+/// Moves paths from one group to another, with the same base folder, pointing their links at the
+/// base folder to the new location.
+///
+/// Files go straight from group to group, so the base folder only ever sees links. Returns the
+/// links that were changed.
+pub fn move_paths(
+    base_dir: &Path,
+    from_group_dir: &Path,
+    to_group_dir: &Path,
+    paths: &[PathBuf],
+) -> Result<Vec<PathBuf>> {
+    let mut relative_paths = paths
+        .iter()
+        .map(|path| group_relative_path(path, base_dir, from_group_dir))
+        .collect::<Result<Vec<_>>>()?;
+
+    // Deduplicate paths inside others
+    let path_trie: PathTrie = relative_paths
+        .iter()
+        .map(|path| from_group_dir.join(path))
+        .collect();
+    relative_paths.retain(|path| !path_trie.contains_ancestor_of(&from_group_dir.join(path)));
+    relative_paths.dedup();
+
+    let paths_to_move = relative_paths
+        .iter()
+        .map(|relative_path| {
+            let from = from_group_dir.join(relative_path);
+            let to = to_group_dir.join(relative_path);
+
+            let conflict_resolution =
+                resolve_conflict(&from, &to, Operation::Move, &ConflictPolicy::default())?;
+            let links = links_into(base_dir, from_group_dir, to_group_dir, relative_path)?;
+
+            Ok(PathToMove {
+                from,
+                to,
+                conflict_resolution,
+                links,
+            })
+        })
+        .collect::<Result<Vec<_>>>()?;
+
+    if paths_to_move.is_empty() {
+        println!("No files to move.");
+        return Ok(vec![]);
+    }
+
+    println!(
+        "Will move {} files: {:#?}",
+        paths_to_move.len(),
+        relative_paths,
+    );
+
+    let mut relinked = vec![];
+
+    for PathToMove {
+        from,
+        to,
+        conflict_resolution,
+        links,
+    } in paths_to_move
+    {
+        // the destination may already have the same file
+        let keep_destination = !conflict_resolution.prepare(&from, &to)?;
+
+        if !keep_destination {
+            cheap_move_with_fallback(&from, &to).wrap_err("Failed to move file between groups")?;
+        }
+
+        for (link, target) in links {
+            utils::replace_symlink(&link, &target)?;
+            println!("Relinked {link:?} to {target:?}");
+            relinked.push(link);
+        }
+
+        if keep_destination {
+            utils::remove_path(&from)?;
+        }
+        remove_empty_parents(&from, from_group_dir)?;
+    }
+
+    Ok(relinked)
+}
+
+/// Renames the group folder, pointing its links at the base folder to the new name. Returns the
+/// links that were changed.
+pub fn rename_group(
+    base_dir: &Path,
+    old_group_dir: &Path,
+    new_group_dir: &Path,
+) -> Result<Vec<PathBuf>> {
+    if !try_exists(old_group_dir)? {
+        bail!("group folder {old_group_dir:?} doesn't exist");
+    }
+    if try_exists(new_group_dir)? {
+        bail!("can't rename the group, {new_group_dir:?} already exists");
+    }
+
+    let links = links_into(base_dir, old_group_dir, new_group_dir, Path::new(""))?;
+
+    fs::rename(old_group_dir, new_group_dir)?;
+    println!("Renamed {old_group_dir:?} to {new_group_dir:?}");
+
+    let mut relinked = vec![];
+    for (link, target) in links {
+        utils::replace_symlink(&link, &target)?;
+        println!("Relinked {link:?} to {target:?}");
+        relinked.push(link);
+    }
+
+    Ok(relinked)
+}
+
+/// Moves paths out of a group into a new one.
+pub fn split_group(
+    base_dir: &Path,
+    group_dir: &Path,
+    new_group_dir: &Path,
+    paths: &[PathBuf],
+) -> Result<Vec<PathBuf>> {
+    if try_exists(new_group_dir)? {
+        bail!("can't split into {new_group_dir:?}, it already exists, use `dotin move` instead");
+    }
+    move_paths(base_dir, group_dir, new_group_dir, paths)
+}
+
+/// Moves every file of the `source` group into `destination`, then removes the `source` folder.
+///
+/// Directories in both groups are merged, conflicts follow the same rules as import, and are all
+/// checked before moving anything.
+pub fn merge_groups(
+    base_dir: &Path,
+    source_dir: &Path,
+    destination_dir: &Path,
+) -> Result<Vec<PathBuf>> {
+    if !try_exists(source_dir)? {
+        bail!("group folder {source_dir:?} doesn't exist");
+    }
+    if !try_exists(destination_dir)? {
+        bail!("group folder {destination_dir:?} doesn't exist, use `dotin rename-group` instead");
+    }
+
+    let mut paths = vec![];
+    collect_paths_to_merge(source_dir, destination_dir, Path::new(""), &mut paths)?;
+
+    let relinked = move_paths(base_dir, source_dir, destination_dir, &paths)?;
+    utils::remove_empty_group_folder(source_dir)?;
+
+    Ok(relinked)
+}
+
+/// Collects the `source` paths to move, descending into directories that exist at both groups.
+fn collect_paths_to_merge(
+    source_dir: &Path,
+    destination_dir: &Path,
+    relative_dir: &Path,
+    paths: &mut Vec<PathBuf>,
+) -> Result<()> {
+    let mut entries = fs::read_dir(source_dir.join(relative_dir))?
+        .map(|entry| Ok(relative_dir.join(entry?.file_name())))
+        .collect::<Result<Vec<_>>>()?;
+    entries.sort();
+
+    for relative_path in entries {
+        let source = source_dir.join(&relative_path);
+        let destination = destination_dir.join(&relative_path);
+
+        let is_dir_at_both = read_file_type(&source)? == FileType::Directory
+            && try_exists(&destination)?
+            && read_file_type(&destination)? == FileType::Directory;
+
+        if is_dir_at_both {
+            collect_paths_to_merge(source_dir, destination_dir, &relative_path, paths)?;
+        } else {
+            paths.push(source);
+        }
+    }
+
+    Ok(())
+}
+
+/// The path relative to the group folder for an argument, which can be a path inside of the
+/// group folder, a path at the base folder, or already relative to the group.
+fn group_relative_path(path: &Path, base_dir: &Path, group_dir: &Path) -> Result<PathBuf> {
+    let absolute = path::absolute(path)?;
+
+    let relative_path = if let Ok(relative_path) = absolute.strip_prefix(group_dir) {
+        relative_path.to_owned()
+    } else if let Ok(relative_path) = absolute.strip_prefix(base_dir)
+        && try_exists(&absolute)?
+    {
+        relative_path.to_owned()
+    } else {
+        path.to_owned()
+    };
+
+    if relative_path.as_os_str().is_empty() || relative_path.is_absolute() {
+        bail!("can't move {path:?}, expected a path inside of the group");
+    }
+    if !try_exists(group_dir.join(&relative_path))? {
+        bail!("couldn't find {path:?} in group folder {group_dir:?}");
+    }
+
+    Ok(relative_path)
+}
+
+/// Links at the base folder pointing into the group path at `relative_path` (or its children),
+/// with their targets pointing into the other group instead.
+fn links_into(
+    base_dir: &Path,
+    from_group_dir: &Path,
+    to_group_dir: &Path,
+    relative_path: &Path,
+) -> Result<Vec<(PathBuf, PathBuf)>> {
+    let tree = FsTree::symlink_read_at(join_relative(from_group_dir, relative_path))
+        .wrap_err("reading group tree to move")?;
+    let mut links = vec![];
+
+    for (_, inner_path) in &tree {
+        let group_relative_path = join_relative(relative_path, &inner_path);
+
+        // alternates are linked at their plain path
+        let link = match plain_path_of_alternate(&group_relative_path) {
+            Some((plain_path, _)) => base_dir.join(plain_path),
+            None => join_relative(base_dir, &group_relative_path),
+        };
+        if !try_exists(&link)? || read_file_type(&link)? != FileType::Symlink {
+            continue;
+        }
+
+        let current_target = fs::read_link(&link)?;
+        let old_target = create_relative_symlink_target_path(
+            &link,
+            &join_relative(from_group_dir, &group_relative_path),
+        );
+        if are_equivalent_symlink_targets(&link, &current_target, &old_target) {
+            let new_target = create_relative_symlink_target_path(
+                &link,
+                &join_relative(to_group_dir, &group_relative_path),
+            );
+            links.push((link, new_target));
+        }
+    }
+
+    Ok(links)
+}
+
+/// Removes the empty directories left above `path`, up to the group folder.
+fn remove_empty_parents(path: &Path, group_dir: &Path) -> Result<()> {
+    for parent in path.ancestors().skip(1) {
+        if parent == group_dir || !parent.starts_with(group_dir) {
+            break;
+        }
+        if fs::read_dir(parent)?.next().is_some() {
+            break;
+        }
+        fs::remove_dir(parent)?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use fs_tree::tree;
+    use pretty_assertions::assert_eq;
+
+    use super::*;
+    use crate::utils::test_utils::cd_to_testdir;
+
+    #[test]
+    fn test_move_paths() {
+        let (_dropper, test_dir) = cd_to_testdir().unwrap();
+
+        tree! {
+            ".zshrc" -> "dotfiles/zsh/.zshrc"
+            ".config": [
+                "starship.toml" -> "../dotfiles/zsh/.config/starship.toml"
+            ]
+            dotfiles: [
+                zsh: [
+                    ".zshrc"
+                    ".config": [
+                        "starship.toml"
+                    ]
+                ]
+            ]
+        }
+        .write_structure_at(".")
+        .unwrap();
+
+        let relinked = move_paths(
+            test_dir,
+            &test_dir.join("dotfiles/zsh"),
+            &test_dir.join("dotfiles/prompt"),
+            &[PathBuf::from(".config/starship.toml")],
+        )
+        .unwrap();
+        assert_eq!(relinked, [test_dir.join(".config/starship.toml")]);
+
+        let expected = tree! {
+            ".zshrc" -> "dotfiles/zsh/.zshrc"
+            ".config": [
+                "starship.toml" -> "../dotfiles/prompt/.config/starship.toml"
+            ]
+            dotfiles: [
+                zsh: [
+                    ".zshrc"
+                ]
+                prompt: [
+                    ".config": [
+                        "starship.toml"
+                    ]
+                ]
+            ]
+        };
+        let result = expected.symlink_read_structure_at(".").unwrap();
+        assert_eq!(result, expected);
+    }
+
+    #[test]
+    fn test_move_paths_checks_conflicts_before_moving() {
+        let (_dropper, test_dir) = cd_to_testdir().unwrap();
+
+        let tree = tree! {
+            dotfiles: [
+                zsh: [
+                    ".zshrc"
+                    ".zshenv"
+                ]
+                shell: [
+                    ".zshenv": [
+                        conflict
+                    ]
+                ]
+            ]
+        };
+        tree.write_structure_at(".").unwrap();
+
+        let error = move_paths(
+            test_dir,
+            &test_dir.join("dotfiles/zsh"),
+            &test_dir.join("dotfiles/shell"),
+            &[".zshrc", ".zshenv"].map(PathBuf::from),
+        )
+        .unwrap_err();
+        assert!(
+            format!("{error:?}").contains("non-empty directory"),
+            "{error:?}"
+        );
+
+        let result = tree.symlink_read_structure_at(".").unwrap();
+        assert_eq!(result, tree);
+    }
+
+    #[test]
+    fn test_rename_group() {
+        let (_dropper, test_dir) = cd_to_testdir().unwrap();
+
+        tree! {
+            ".config": [
+                i3: [
+                    config -> "../../dotfiles/wm/.config/i3/config"
+                ]
+            ]
+            ".Xresources" -> "dotfiles/wm/.Xresources##host.desk"
+            dotfiles: [
+                wm: [
+                    ".config": [
+                        i3: [
+                            config
+                        ]
+                    ]
+                    ".Xresources##host.desk"
+                ]
+                taken: []
+            ]
+        }
+        .write_structure_at(".")
+        .unwrap();
+
+        let error = rename_group(
+            test_dir,
+            &test_dir.join("dotfiles/wm"),
+            &test_dir.join("dotfiles/taken"),
+        )
+        .unwrap_err();
+        assert!(error.to_string().contains("already exists"), "{error}");
+
+        rename_group(
+            test_dir,
+            &test_dir.join("dotfiles/wm"),
+            &test_dir.join("dotfiles/i3"),
+        )
+        .unwrap();
+
+        let expected = tree! {
+            ".config": [
+                i3: [
+                    config -> "../../dotfiles/i3/.config/i3/config"
+                ]
+            ]
+            ".Xresources" -> "dotfiles/i3/.Xresources##host.desk"
+            dotfiles: [
+                i3: [
+                    ".config": [
+                        i3: [
+                            config
+                        ]
+                    ]
+                    ".Xresources##host.desk"
+                ]
+                taken: []
+            ]
+        };
+        let result = expected.symlink_read_structure_at(".").unwrap();
+        assert_eq!(result, expected);
+    }
+
+    #[test]
+    fn test_merge_groups() {
+        let (_dropper, test_dir) = cd_to_testdir().unwrap();
+
+        tree! {
+            ".config": [
+                rofi: [
+                    "config.rasi" -> "../../dotfiles/rofi/.config/rofi/config.rasi"
+                ]
+                dunst -> "../dotfiles/dunst/.config/dunst"
+            ]
+            dotfiles: [
+                rofi: [
+                    ".config": [
+                        rofi: [
+                            "config.rasi"
+                        ]
+                    ]
+                ]
+                dunst: [
+                    ".config": [
+                        dunst: [
+                            dunstrc
+                        ]
+                    ]
+                ]
+            ]
+        }
+        .write_structure_at(".")
+        .unwrap();
+
+        merge_groups(
+            test_dir,
+            &test_dir.join("dotfiles/dunst"),
+            &test_dir.join("dotfiles/rofi"),
+        )
+        .unwrap();
+
+        let expected = tree! {
+            ".config": [
+                rofi: [
+                    "config.rasi" -> "../../dotfiles/rofi/.config/rofi/config.rasi"
+                ]
+                dunst -> "../dotfiles/rofi/.config/dunst"
+            ]
+            dotfiles: [
+                rofi: [
+                    ".config": [
+                        rofi: [
+                            "config.rasi"
+                        ]
+                        dunst: [
+                            dunstrc
+                        ]
+                    ]
+                ]
+            ]
+        };
+        let result = expected.symlink_read_structure_at(".").unwrap();
+        assert_eq!(result, expected);
+    }
+
+    #[test]
+    fn test_merge_groups_detects_overlaps() {
+        let (_dropper, test_dir) = cd_to_testdir().unwrap();
+
+        let tree = tree! {
+            dotfiles: [
+                a: [
+                    ".config": [
+                        same
+                        different
+                    ]
+                ]
+                b: [
+                    ".config": [
+                        same
+                        different: [
+                            file
+                        ]
+                    ]
+                ]
+            ]
+        };
+        tree.write_structure_at(".").unwrap();
+
+        let error = merge_groups(
+            test_dir,
+            &test_dir.join("dotfiles/a"),
+            &test_dir.join("dotfiles/b"),
+        )
+        .unwrap_err();
+        assert!(
+            format!("{error:?}").contains("non-empty directory"),
+            "{error:?}"
+        );
+
+        let result = tree.symlink_read_structure_at(".").unwrap();
+        assert_eq!(result, tree);
+    }
+}
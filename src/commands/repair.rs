@@ -0,0 +1,183 @@
+use std::path::{Path, PathBuf};
+
+use eyre::{OptionExt, WrapErr};
+use fs_err as fs;
+use fs_tree::FsTree;
+
+use crate::{
+    Result,
+    utils::{self, are_equivalent_symlink_targets, create_relative_symlink_target_path},
+};
+
+#[derive(Debug)]
+struct LinkToRepair {
+    base_absolute: PathBuf,
+    symlink_target: PathBuf,
+}
+
+/// Rewrites links that point to the right path inside of the group, but under another dotfiles
+/// folder, e.g. after moving `~/dotfiles` to `~/.dotfiles`.
+pub fn repair(base_dir: &Path, group_dir: &Path) -> Result<()> {
+    let group_name = group_dir
+        .file_name()
+        .ok_or_eyre("Internal error, malformed group folder")?;
+
+    let group_tree = FsTree::symlink_read_at(group_dir).wrap_err("reading dotfiles folder tree")?;
+
+    let base_tree = group_tree
+        .symlink_read_structure_at(base_dir)
+        .wrap_err("reading structured file tree at base folder")?;
+
+    let mut links_to_repair = vec![];
+
+    for (node, relative_path) in &base_tree {
+        let Some(current_target) = node.target() else {
+            continue;
+        };
+
+        let base_absolute = base_dir.join(&relative_path);
+        let dotfile_absolute = group_dir.join(&relative_path);
+        let symlink_target = create_relative_symlink_target_path(&base_absolute, &dotfile_absolute);
+
+        if are_equivalent_symlink_targets(&base_absolute, current_target, &symlink_target) {
+            continue;
+        }
+
+        // Same path inside of the group, but the dotfiles folder is somewhere else
+        if current_target.ends_with(Path::new(group_name).join(&relative_path))
+            && is_old_dotfiles_folder(&base_absolute, current_target, &relative_path)?
+        {
+            links_to_repair.push(LinkToRepair {
+                base_absolute,
+                symlink_target,
+            });
+        } else {
+            println!(
+                "ERROR: {base_absolute:?} points to {current_target:?}, which isn't inside of a group named {group_name:?} in a dotfiles folder, skipping it"
+            );
+        }
+    }
+
+    if links_to_repair.is_empty() {
+        println!("No links to repair.");
+        return Ok(());
+    }
+
+    println!("Will repair {} links:", links_to_repair.len());
+    for LinkToRepair {
+        base_absolute,
+        symlink_target,
+    } in &links_to_repair
+    {
+        println!("  {base_absolute:?} -> {symlink_target:?}");
+    }
+
+    for LinkToRepair {
+        base_absolute,
+        symlink_target,
+    } in &links_to_repair
+    {
+        fs::remove_file(base_absolute).wrap_err("Failed to delete symlink")?;
+        utils::create_symlink(base_absolute, symlink_target)?;
+    }
+
+    Ok(())
+}
+
+/// Checks that the folder holding the group in `current_target` looks like a dotfiles folder,
+/// by its name, or because it still contains a folder for the group.
+fn is_old_dotfiles_folder(
+    base_absolute: &Path,
+    current_target: &Path,
+    relative_path: &Path,
+) -> Result<bool> {
+    let Some(old_group_folder) = current_target
+        .ancestors()
+        .nth(relative_path.components().count())
+    else {
+        return Ok(false);
+    };
+
+    let is_candidate_name = old_group_folder
+        .parent()
+        .and_then(Path::file_name)
+        .and_then(|name| name.to_str())
+        .is_some_and(|name| utils::DOTFILES_FOLDER_CANDIDATES.contains(&name));
+    if is_candidate_name {
+        return Ok(true);
+    }
+
+    // Relative targets start at the link's folder
+    let link_folder = base_absolute.parent().unwrap_or(Path::new("."));
+    let old_group_node = utils::read_node(&link_folder.join(old_group_folder))?;
+    Ok(old_group_node.is_some_and(|node| node.is_dir()))
+}
+
+#[cfg(test)]
+mod tests {
+    use fs_tree::tree;
+    use pretty_assertions::assert_eq;
+
+    use super::*;
+    use crate::utils::test_utils::cd_to_testdir;
+
+    #[test]
+    fn test_repair() {
+        let (_dropper, test_dir) = cd_to_testdir().unwrap();
+        let old_absolute_target = test_dir.join("old_dotfiles/i3/.config/i3/theme");
+        let candidate_absolute_target = test_dir.join("dots/i3/.config/i3/layout");
+        let backup_absolute_target = test_dir.join("mnt/backup/i3/.config/i3/mirrored");
+
+        let home = tree! {
+            ".config": [
+                i3: [
+                    config -> "../../dotfiles/i3/.config/i3/config"
+                    theme -> {old_absolute_target}
+                    layout -> {candidate_absolute_target}
+                    mirrored -> {backup_absolute_target.clone()}
+                    foreign -> "../../somewhere/else/foreign"
+                ]
+            ]
+        };
+        let old_dotfiles = tree! {
+            old_dotfiles: [
+                i3: []
+            ]
+        };
+        let dotfiles = tree! {
+            ".dotfiles": [
+                i3: [
+                    ".config": [
+                        i3: [
+                            config
+                            theme
+                            layout
+                            mirrored
+                            foreign
+                        ]
+                    ]
+                ]
+            ]
+        };
+        let expected_home = tree! {
+            ".config": [
+                i3: [
+                    config -> "../../.dotfiles/i3/.config/i3/config"
+                    theme -> "../../.dotfiles/i3/.config/i3/theme"
+                    layout -> "../../.dotfiles/i3/.config/i3/layout"
+                    mirrored -> {backup_absolute_target}
+                    foreign -> "../../somewhere/else/foreign"
+                ]
+            ]
+        };
+
+        home.write_structure_at(".").unwrap();
+        old_dotfiles.write_structure_at(".").unwrap();
+        dotfiles.write_structure_at(".").unwrap();
+
+        repair(test_dir, &test_dir.join(".dotfiles/i3")).unwrap();
+
+        let result = expected_home.symlink_read_structure_at(".").unwrap();
+        assert_eq!(result, expected_home);
+    }
+}
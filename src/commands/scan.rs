@@ -0,0 +1,447 @@
+use std::{
+    io::Read,
+    path::{Path, PathBuf},
+};
+
+use fs_err as fs;
+use glob::{MatchOptions, Pattern};
+
+use crate::{
+    Result,
+    catalog::{Catalog, group_name_for_file},
+    utils::normalize_path,
+};
+
+/// Caches, state and data at the base folder, which aren't configs.
+const IGNORED: &[&str] = &[
+    ".Trash*",
+    ".ICEauthority",
+    ".Xauthority",
+    ".android",
+    ".bash_history",
+    ".bash_logout",
+    ".cache",
+    ".cargo",
+    ".config/BraveSoftware",
+    ".config/Code/Cache*",
+    ".config/chromium",
+    ".config/google-chrome",
+    ".config/pulse",
+    ".config/vivaldi",
+    ".dbus",
+    ".gradle",
+    ".java",
+    ".lesshst",
+    ".local",
+    ".m2",
+    ".mozilla",
+    ".node_repl_history",
+    ".npm",
+    ".nv",
+    ".pki",
+    ".python_history",
+    ".rustup",
+    ".steam",
+    ".sudo_as_admin_successful",
+    ".thumbnails",
+    ".thunderbird",
+    ".var",
+    ".viminfo",
+    ".vscode",
+    ".wget-hsts",
+    ".wine",
+    ".xsession-errors*",
+    ".zcompdump*",
+    ".zsh_history",
+];
+
+const MATCH_OPTIONS: MatchOptions = MatchOptions {
+    case_sensitive: true,
+    require_literal_separator: true,
+    require_literal_leading_dot: false,
+};
+
+/// Folders that hold configs, relative to the base folder, their entries are the candidates.
+const ROOTS: &[&str] = &["", ".config"];
+
+/// Stop summarizing a directory after this many entries, it's probably not hand-written.
+const MAX_ENTRIES: usize = 1000;
+
+/// Words in names of files that are written by programs, not by hand.
+const STATE_WORDS: &[&str] = &["cache", "lock", "log", "logs", "session", "state", "trash"];
+
+/// A path at the base folder that isn't managed by dotin.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ScanCandidate {
+    /// Path relative to the base folder.
+    pub path: PathBuf,
+    /// Suggested group name.
+    pub group: String,
+    /// Higher is more likely a hand-written config.
+    pub score: i32,
+    pub reasons: Vec<&'static str>,
+}
+
+/// What was found inside of a candidate, up to the depth limit.
+#[derive(Debug, Default)]
+struct Summary {
+    files: usize,
+    text_files: usize,
+    bytes: u64,
+    /// Symlinks into the dotfiles folder.
+    managed_links: usize,
+    other_links: usize,
+    /// Entries that couldn't be read, like root-owned leftovers of `sudo`.
+    unreadable: usize,
+    truncated: bool,
+}
+
+/// Prints unmanaged paths of the base folder, most likely configs first, as `dotin import`
+/// commands.
+pub fn scan(
+    base_dir: &Path,
+    home_dir: &Path,
+    dotfiles_folder: &Path,
+    catalog: &Catalog,
+    depth: usize,
+) -> Result<()> {
+    let candidates = find_unmanaged(base_dir, dotfiles_folder, catalog, depth)?;
+
+    if candidates.is_empty() {
+        println!("No unmanaged configs found.");
+        return Ok(());
+    }
+
+    println!(
+        "Found {} unmanaged paths, most likely configs first:",
+        candidates.len()
+    );
+    for candidate in &candidates {
+        let path = if base_dir == home_dir {
+            format!("~/{}", shell_word(&candidate.path))
+        } else {
+            shell_word(&base_dir.join(&candidate.path))
+        };
+
+        println!(
+            "  dotin import {} {path}  # score {}: {}",
+            shell_word(Path::new(&candidate.group)),
+            candidate.score,
+            candidate.reasons.join(", "),
+        );
+    }
+
+    Ok(())
+}
+
+/// Entries of [`ROOTS`] that aren't ignored nor links into the dotfiles folder, ranked.
+pub fn find_unmanaged(
+    base_dir: &Path,
+    dotfiles_folder: &Path,
+    catalog: &Catalog,
+    depth: usize,
+) -> Result<Vec<ScanCandidate>> {
+    let ignored = IGNORED
+        .iter()
+        .map(|pattern| Pattern::new(pattern).expect("valid pattern"))
+        .collect::<Vec<_>>();
+    let dotfiles_folder = normalize_path(dotfiles_folder);
+
+    let mut candidates = vec![];
+
+    for root in ROOTS {
+        let root_dir = base_dir.join(root);
+        let Ok(entries) = fs::read_dir(&root_dir) else {
+            continue;
+        };
+
+        for entry in entries {
+            let entry = entry?;
+            let path = Path::new(root).join(entry.file_name());
+            let absolute = normalize_path(&base_dir.join(&path));
+
+            let is_hidden = entry.file_name().to_string_lossy().starts_with('.');
+            let is_root = ROOTS.iter().any(|root| Path::new(root) == path);
+            let is_dotfiles_related =
+                absolute.starts_with(&dotfiles_folder) || dotfiles_folder.starts_with(&absolute);
+
+            if (root.is_empty() && !is_hidden)
+                || is_root
+                || is_dotfiles_related
+                || ignored
+                    .iter()
+                    .any(|pattern| pattern.matches_path_with(&path, MATCH_OPTIONS))
+            {
+                continue;
+            }
+
+            let mut summary = Summary::default();
+            summarize(&absolute, &dotfiles_folder, depth, &mut summary);
+
+            // fully managed already, or nothing in it
+            if summary.files + summary.other_links == 0 {
+                continue;
+            }
+
+            candidates.push(rank(path, &summary, catalog));
+        }
+    }
+
+    candidates.sort_by(|a, b| b.score.cmp(&a.score).then_with(|| a.path.cmp(&b.path)));
+    Ok(candidates)
+}
+
+/// Adds what is at `path` to the summary, entries that can't be read are skipped with a warning.
+fn summarize(path: &Path, dotfiles_folder: &Path, depth: usize, summary: &mut Summary) {
+    if let Err(error) = try_summarize(path, dotfiles_folder, depth, summary) {
+        println!("WARNING: {error}, skipping it");
+        summary.unreadable += 1;
+    }
+}
+
+fn try_summarize(
+    path: &Path,
+    dotfiles_folder: &Path,
+    depth: usize,
+    summary: &mut Summary,
+) -> Result<()> {
+    if summary.files + summary.other_links >= MAX_ENTRIES {
+        summary.truncated = true;
+        return Ok(());
+    }
+
+    let metadata = fs::symlink_metadata(path)?;
+    let file_type = metadata.file_type();
+
+    if file_type.is_symlink() {
+        let target = fs::read_link(path)?;
+        let parent = path.parent().unwrap_or(Path::new("/"));
+        if normalize_path(&parent.join(target)).starts_with(dotfiles_folder) {
+            summary.managed_links += 1;
+        } else {
+            summary.other_links += 1;
+        }
+    } else if file_type.is_dir() {
+        if depth == 0 {
+            summary.truncated = true;
+            return Ok(());
+        }
+        for entry in fs::read_dir(path)? {
+            summarize(&entry?.path(), dotfiles_folder, depth - 1, summary);
+        }
+    } else if file_type.is_file() {
+        summary.files += 1;
+        summary.bytes += metadata.len();
+        if is_text_file(path)? {
+            summary.text_files += 1;
+        }
+    }
+
+    Ok(())
+}
+
+/// Checks the start of the file for NUL bytes and invalid UTF-8.
+fn is_text_file(path: &Path) -> Result<bool> {
+    let mut buffer = [0; 1024];
+    let read = fs::File::open(path)?.read(&mut buffer)?;
+    let start = &buffer[..read];
+
+    if start.contains(&0) {
+        return Ok(false);
+    }
+    // the buffer may end in the middle of a character
+    match std::str::from_utf8(start) {
+        Ok(_) => Ok(true),
+        Err(error) => Ok(error.error_len().is_none()),
+    }
+}
+
+fn rank(path: PathBuf, summary: &Summary, catalog: &Catalog) -> ScanCandidate {
+    let mut score = 0;
+    let mut reasons = vec![];
+    let mut add = |points, reason| {
+        score += points;
+        reasons.push(reason);
+    };
+
+    let known_group = catalog.known_group_for(&path);
+    if known_group.is_some() {
+        add(4, "known config");
+    }
+
+    let name = path.file_name().unwrap_or_default().to_string_lossy();
+    let name = name.to_lowercase();
+    if name
+        .split(|char: char| !char.is_ascii_alphanumeric())
+        .any(|word| STATE_WORDS.contains(&word) || word.ends_with("history"))
+    {
+        add(-3, "looks like state");
+    } else if path.starts_with(".config")
+        || name.ends_with("rc")
+        || name.contains("profile")
+        || [".conf", ".toml", ".yaml", ".yml", ".ini", ".lua", ".json"]
+            .iter()
+            .any(|extension| name.ends_with(extension))
+    {
+        add(2, "config-like name");
+    }
+
+    match summary.bytes {
+        ..65_536 => add(2, "small"),
+        65_536..1_048_576 => {}
+        _ => add(-3, "large"),
+    }
+
+    if summary.truncated || summary.files > 100 {
+        add(-2, "many files");
+    }
+
+    if summary.files > 0 && summary.text_files == summary.files {
+        add(1, "text");
+    } else if summary.text_files * 2 < summary.files {
+        add(-2, "mostly binary");
+    }
+
+    if summary.files == 0 {
+        add(-1, "only symlinks");
+    }
+
+    if summary.managed_links > 0 {
+        add(1, "partially managed");
+    }
+
+    if summary.unreadable > 0 {
+        add(-1, "partly unreadable");
+    }
+
+    let group = match known_group {
+        Some(group) => group.to_owned(),
+        None => group_name_for_file(&path.file_name().unwrap_or_default().to_string_lossy()),
+    };
+
+    ScanCandidate {
+        path,
+        group,
+        score,
+        reasons,
+    }
+}
+
+/// Quotes the path for a shell, if needed.
+fn shell_word(path: &Path) -> String {
+    let path = path.to_string_lossy();
+    let is_safe = |char: char| char.is_ascii_alphanumeric() || "._/+-@%:,=".contains(char);
+
+    if !path.is_empty() && path.chars().all(is_safe) {
+        path.into_owned()
+    } else {
+        format!("'{}'", path.replace('\'', r"'\''"))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::os::unix::fs::PermissionsExt;
+
+    use fs_tree::tree;
+    use indexmap::IndexMap;
+    use pretty_assertions::assert_eq;
+
+    use super::*;
+    use crate::utils::test_utils::cd_to_testdir;
+
+    #[test]
+    fn test_find_unmanaged() {
+        let (_dropper, test_dir) = cd_to_testdir().unwrap();
+
+        tree! {
+            ".zshrc"
+            ".npmrc"
+            ".bash_history"
+            Documents: [
+                notes
+            ]
+            ".cache": [
+                thumbnails
+            ]
+            ".config": [
+                nvim: [
+                    "init.lua"
+                ]
+                git: [
+                    config -> "../../dotfiles/git/.config/git/config"
+                ]
+                "weird app": [
+                    "settings.json"
+                ]
+            ]
+            dotfiles: [
+                git: [
+                    ".config": [
+                        git: [
+                            config
+                        ]
+                    ]
+                ]
+            ]
+        }
+        .write_structure_at(".")
+        .unwrap();
+        fs::write(".config/nvim/init.lua", "vim.o.number = true\n").unwrap();
+
+        let catalog = Catalog::new(&IndexMap::new());
+        let candidates = find_unmanaged(test_dir, &test_dir.join("dotfiles"), &catalog, 4).unwrap();
+
+        let found = candidates
+            .iter()
+            .map(|candidate| (candidate.path.to_str().unwrap(), candidate.group.as_str()))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            found,
+            [
+                (".config/nvim", "nvim"),
+                (".zshrc", "zsh"),
+                (".config/weird app", "weird app"),
+                (".npmrc", "npm"),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_find_unmanaged_skips_unreadable_entries() {
+        let (_dropper, test_dir) = cd_to_testdir().unwrap();
+
+        tree! {
+            ".config": [
+                nvim: [
+                    "init.lua"
+                    "root-owned": [
+                        secret
+                    ]
+                ]
+            ]
+        }
+        .write_structure_at(".")
+        .unwrap();
+        fs::set_permissions(".config/nvim/root-owned", PermissionsExt::from_mode(0o000)).unwrap();
+
+        let catalog = Catalog::new(&IndexMap::new());
+        let result = find_unmanaged(test_dir, &test_dir.join("dotfiles"), &catalog, 4);
+        fs::set_permissions(".config/nvim/root-owned", PermissionsExt::from_mode(0o755)).unwrap();
+        assert_eq!(result.unwrap()[0].path, Path::new(".config/nvim"));
+
+        // permissions don't stop root, so check the fallback with a missing path too
+        let mut summary = Summary::default();
+        summarize(Path::new("missing"), test_dir, 4, &mut summary);
+        assert_eq!(summary.unreadable, 1);
+    }
+
+    #[test]
+    fn test_shell_word() {
+        assert_eq!(shell_word(Path::new(".zshrc")), ".zshrc");
+        assert_eq!(
+            shell_word(Path::new(".config/it's here")),
+            r"'.config/it'\''s here'"
+        );
+    }
+}
@@ -0,0 +1,417 @@
+use std::{
+    fmt,
+    path::{Path, PathBuf},
+};
+
+use eyre::WrapErr;
+use fs_err as fs;
+use fs_tree::FsTree;
+
+use crate::{
+    Result,
+    alternate::{choose_alternates, plain_path_of_alternate},
+    permissions::{Permissions, declared_path},
+    template::{TemplateVariables, render_file, template_output_path},
+    utils::{
+        FileType, are_equivalent_symlink_targets, create_relative_symlink_target_path,
+        find_leftover_temporaries_at, is_leftover_temporary, join_relative, read_file_type,
+        read_node, try_exists,
+    },
+};
+
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord)]
+pub enum PathStatus {
+    Linked,
+    NotLinked,
+    /// Something else is at the base folder, with a description of it.
+    Conflict(String),
+    Rendered,
+    /// The output differs from rendering the template now, either the template, the variables,
+    /// or the output itself changed.
+    Stale,
+    NotRendered,
+    /// No alternate matches this machine.
+    NoAlternate,
+    /// The group file mode differs from the one declared in the config.
+    WrongMode {
+        actual: u32,
+        declared: u32,
+    },
+    /// A temporary left at the base folder by an interrupted move.
+    LeftoverTemporary,
+    /// A temporary left in the group folder by an interrupted move.
+    LeftoverGroupTemporary,
+}
+
+/// Status of a path at the base folder.
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord)]
+pub struct StatusEntry {
+    /// Path relative to the base folder.
+    pub path: PathBuf,
+    pub status: PathStatus,
+    /// The group file, if its path differs from `path` (templates and alternates).
+    pub source: Option<PathBuf>,
+}
+
+impl fmt::Display for StatusEntry {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}: {:?}", self.status, self.path)?;
+        if let Some(source) = &self.source {
+            write!(f, " (from {source:?})")?;
+        }
+        Ok(())
+    }
+}
+
+impl fmt::Display for PathStatus {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            PathStatus::Linked => write!(f, "linked"),
+            PathStatus::NotLinked => write!(f, "not linked"),
+            PathStatus::Conflict(description) => write!(f, "conflict, {description}"),
+            PathStatus::Rendered => write!(f, "rendered"),
+            PathStatus::Stale => write!(f, "stale, differs from the rendered template"),
+            PathStatus::NotRendered => write!(f, "not rendered"),
+            PathStatus::NoAlternate => write!(f, "no alternate matches this machine"),
+            PathStatus::WrongMode { actual, declared } => {
+                write!(f, "mode is {actual:04o} instead of {declared:04o}")
+            }
+            PathStatus::LeftoverTemporary => write!(
+                f,
+                "left by an interrupted move, removed by the next link, or move into its folder"
+            ),
+            PathStatus::LeftoverGroupTemporary => write!(
+                f,
+                "left in the group folder by an interrupted move, removed by the next link"
+            ),
+        }
+    }
+}
+
+/// Prints the status of every file in the group.
+pub fn status(
+    base_dir: &Path,
+    group_dir: &Path,
+    variables: &TemplateVariables,
+    permissions: &Permissions,
+) -> Result<()> {
+    for entry in group_status(base_dir, group_dir, variables, permissions)? {
+        println!("{entry}");
+    }
+    Ok(())
+}
+
+/// Status of every leaf of the group, and of files with a mode other than the declared one.
+pub fn group_status(
+    base_dir: &Path,
+    group_dir: &Path,
+    variables: &TemplateVariables,
+    permissions: &Permissions,
+) -> Result<Vec<StatusEntry>> {
+    let group_tree = FsTree::symlink_read_at(group_dir).wrap_err("reading dotfiles folder tree")?;
+
+    let base_tree = group_tree
+        .symlink_read_structure_at(base_dir)
+        .wrap_err("reading structured file tree at base folder")?;
+
+    let alternates = choose_alternates(&group_tree, variables)?;
+
+    let mut entries = vec![];
+    let mut alternates_seen = vec![];
+
+    for (group_node, relative_path) in group_tree.iter().min_depth(1) {
+        // alternates are linked as a whole, skip their children
+        if alternates_seen
+            .iter()
+            .any(|alternate| relative_path.starts_with(alternate))
+        {
+            continue;
+        }
+
+        // reported once, for the temporary itself
+        if let Some(leftover) = relative_path
+            .ancestors()
+            .find(|path| is_leftover_temporary(path))
+        {
+            if leftover == relative_path {
+                entries.push(StatusEntry {
+                    path: relative_path,
+                    status: PathStatus::LeftoverGroupTemporary,
+                    source: None,
+                });
+            }
+            continue;
+        }
+
+        let dotfile_absolute = group_dir.join(&relative_path);
+
+        if let Some((plain_path, _)) = plain_path_of_alternate(&relative_path) {
+            alternates_seen.push(relative_path.clone());
+
+            // reported once, for the chosen alternate
+            if let Some(chosen) = alternates.get(&plain_path).and_then(Option::as_ref)
+                && *chosen == relative_path
+            {
+                let base_absolute = base_dir.join(&plain_path);
+                let base_node = read_node(&base_absolute)?;
+                entries.push(StatusEntry {
+                    status: link_status(&base_absolute, &dotfile_absolute, base_node.as_ref()),
+                    path: plain_path,
+                    source: Some(relative_path),
+                });
+            }
+            continue;
+        }
+
+        if !group_node.is_leaf() {
+            continue;
+        }
+
+        if group_node.is_regular()
+            && let Some(output_relative_path) = template_output_path(&relative_path)
+        {
+            let output_absolute = base_dir.join(&output_relative_path);
+            entries.push(StatusEntry {
+                path: output_relative_path,
+                status: template_status(&dotfile_absolute, &output_absolute, variables)?,
+                source: Some(relative_path),
+            });
+            continue;
+        }
+
+        let base_absolute = base_dir.join(&relative_path);
+        entries.push(StatusEntry {
+            status: link_status(
+                &base_absolute,
+                &dotfile_absolute,
+                base_tree.get(&relative_path),
+            ),
+            path: relative_path,
+            source: None,
+        });
+    }
+
+    for (plain_path, chosen) in alternates {
+        if chosen.is_none() {
+            entries.push(StatusEntry {
+                path: plain_path,
+                status: PathStatus::NoAlternate,
+                source: None,
+            });
+        }
+    }
+
+    let mut parents = entries
+        .iter()
+        .filter_map(|entry| entry.path.parent().map(Path::to_owned))
+        .collect::<Vec<_>>();
+    parents.sort();
+    parents.dedup();
+    for parent in parents {
+        let parent_absolute = join_relative(base_dir, &parent);
+        if !try_exists(&parent_absolute)?
+            || read_file_type(&parent_absolute)? != FileType::Directory
+        {
+            continue;
+        }
+
+        for leftover in find_leftover_temporaries_at(&parent_absolute)? {
+            entries.push(StatusEntry {
+                path: parent.join(leftover.file_name().unwrap_or_default()),
+                status: PathStatus::LeftoverTemporary,
+                source: None,
+            });
+        }
+    }
+
+    for mismatch in permissions.mismatches(group_dir)? {
+        let path = declared_path(&mismatch.path);
+        entries.push(StatusEntry {
+            source: (path != mismatch.path).then_some(mismatch.path),
+            path,
+            status: PathStatus::WrongMode {
+                actual: mismatch.actual,
+                declared: mismatch.declared,
+            },
+        });
+    }
+
+    Ok(entries)
+}
+
+fn link_status(
+    base_absolute: &Path,
+    dotfile_absolute: &Path,
+    base_node: Option<&FsTree>,
+) -> PathStatus {
+    let symlink_target = create_relative_symlink_target_path(base_absolute, dotfile_absolute);
+
+    match base_node {
+        None => PathStatus::NotLinked,
+        Some(base_node) => match base_node.target() {
+            Some(target)
+                if are_equivalent_symlink_targets(base_absolute, target, &symlink_target) =>
+            {
+                PathStatus::Linked
+            }
+            Some(target) => PathStatus::Conflict(format!("symlink points to {target:?}")),
+            None => PathStatus::Conflict(format!("{} is in the way", base_node.variant_str())),
+        },
+    }
+}
+
+fn template_status(
+    template_path: &Path,
+    output_path: &Path,
+    variables: &TemplateVariables,
+) -> Result<PathStatus> {
+    if !try_exists(output_path)? {
+        return Ok(PathStatus::NotRendered);
+    }
+
+    let file_type = read_file_type(output_path)?;
+    if file_type != FileType::Regular {
+        return Ok(PathStatus::Conflict(format!("{file_type} is in the way")));
+    }
+
+    if fs::read(output_path)? == render_file(template_path, variables)?.as_bytes() {
+        Ok(PathStatus::Rendered)
+    } else {
+        Ok(PathStatus::Stale)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::os::unix::fs::PermissionsExt;
+
+    use fs_tree::tree;
+    use pretty_assertions::assert_eq;
+
+    use super::*;
+    use crate::utils::{TEMPORARY_PREFIX, test_utils::cd_to_testdir};
+
+    #[test]
+    fn test_group_status() {
+        let (_dropper, test_dir) = cd_to_testdir().unwrap();
+
+        let home = tree! {
+            linked -> "dotfiles/group/linked"
+            alternate -> "dotfiles/group/alternate##host.desk"
+            wrong_target -> "elsewhere"
+            regular
+            fresh
+            stale
+        };
+        let dotfiles = tree! {
+            dotfiles: [
+                group: [
+                    linked
+                    "alternate##default"
+                    "alternate##host.desk"
+                    "no_match##host.laptop"
+                    "no_match##user.other"
+                    missing
+                    wrong_target
+                    regular
+                    "fresh.dotin.tmpl"
+                    "stale.dotin.tmpl"
+                    "unrendered.dotin.tmpl"
+                    "private##default"
+                ]
+            ]
+        };
+
+        home.write_structure_at(".").unwrap();
+        dotfiles.write_structure_at(".").unwrap();
+        // PIDs never get this high, so this process isn't running
+        let leftover = format!("{TEMPORARY_PREFIX}4294967295-regular");
+        fs::write(test_dir.join(&leftover), "").unwrap();
+        let group_leftover = format!("{TEMPORARY_PREFIX}4294967295-moved");
+        fs::create_dir(test_dir.join("dotfiles/group").join(&group_leftover)).unwrap();
+        fs::write(
+            test_dir
+                .join("dotfiles/group")
+                .join(&group_leftover)
+                .join("a"),
+            "",
+        )
+        .unwrap();
+        for name in ["fresh", "stale", "unrendered"] {
+            let template_path = test_dir.join(format!("dotfiles/group/{name}.dotin.tmpl"));
+            fs::write(template_path, "host = {{ hostname }}").unwrap();
+        }
+        fs::write(test_dir.join("fresh"), "host = desk").unwrap();
+        fs::write(test_dir.join("stale"), "host = laptop").unwrap();
+        let private_path = test_dir.join("dotfiles/group/private##default");
+        fs::set_permissions(&private_path, PermissionsExt::from_mode(0o644)).unwrap();
+        let permissions = Permissions::parse(
+            &[("private".to_owned(), "0600".to_owned())]
+                .into_iter()
+                .collect(),
+        )
+        .unwrap();
+
+        let mut variables = TemplateVariables::default();
+        variables.inner.insert("hostname".into(), "desk".into());
+
+        let mut result = group_status(
+            test_dir,
+            &test_dir.join("dotfiles/group"),
+            &variables,
+            &permissions,
+        )
+        .unwrap();
+        result.sort();
+
+        let expected = [
+            (
+                group_leftover.as_str(),
+                PathStatus::LeftoverGroupTemporary,
+                None,
+            ),
+            (leftover.as_str(), PathStatus::LeftoverTemporary, None),
+            (
+                "alternate",
+                PathStatus::Linked,
+                Some("alternate##host.desk"),
+            ),
+            ("fresh", PathStatus::Rendered, Some("fresh.dotin.tmpl")),
+            ("linked", PathStatus::Linked, None),
+            ("missing", PathStatus::NotLinked, None),
+            ("no_match", PathStatus::NoAlternate, None),
+            ("private", PathStatus::NotLinked, Some("private##default")),
+            (
+                "private",
+                PathStatus::WrongMode {
+                    actual: 0o644,
+                    declared: 0o600,
+                },
+                Some("private##default"),
+            ),
+            (
+                "regular",
+                PathStatus::Conflict("regular file is in the way".into()),
+                None,
+            ),
+            ("stale", PathStatus::Stale, Some("stale.dotin.tmpl")),
+            (
+                "unrendered",
+                PathStatus::NotRendered,
+                Some("unrendered.dotin.tmpl"),
+            ),
+            (
+                "wrong_target",
+                PathStatus::Conflict("symlink points to \"elsewhere\"".into()),
+                None,
+            ),
+        ]
+        .map(|(path, status, source)| StatusEntry {
+            path: PathBuf::from(path),
+            status,
+            source: source.map(PathBuf::from),
+        });
+
+        assert_eq!(result, expected);
+    }
+}
@@ -1,12 +1,36 @@
-use std::path::Path;
+use std::{cmp::Reverse, path::Path};
 
 use eyre::WrapErr;
 use fs_err as fs;
 use fs_tree::FsTree;
 
-use crate::{Result, utils::create_relative_symlink_target_path};
+use crate::{
+    Result,
+    alternate::plain_path_of_alternate,
+    state::GroupState,
+    template::{TemplateVariables, render_file, template_output_path},
+    utils::{
+        self, FileType, are_equivalent_symlink_targets, create_relative_symlink_target_path,
+        normalize_path, read_file_type, read_node, try_exists,
+    },
+};
 
-pub fn unlink(base_dir: &Path, group_dir: &Path) -> Result<()> {
+#[derive(Debug, Default, Clone)]
+pub struct UnlinkOptions {
+    /// Move backups made by `link --backup` back into place.
+    pub restore_backups: bool,
+    /// Variables used to check that rendered templates weren't modified.
+    pub template_variables: TemplateVariables,
+}
+
+/// Removes the group links from the base folder, and the links and directories `state` says
+/// `link` created (directories once they're empty).
+pub fn unlink(
+    base_dir: &Path,
+    group_dir: &Path,
+    options: &UnlinkOptions,
+    state: &mut GroupState,
+) -> Result<()> {
     let group_tree = FsTree::symlink_read_at(group_dir).wrap_err("reading dotfiles folder tree")?;
 
     let base_tree = group_tree
@@ -23,9 +47,10 @@ pub fn unlink(base_dir: &Path, group_dir: &Path) -> Result<()> {
         let symlink_target = create_relative_symlink_target_path(&base_absolute, &dotfile_absolute);
 
         // unlink if the link points to the expected target
-        if symlink_target == current_target {
+        if are_equivalent_symlink_targets(&base_absolute, current_target, &symlink_target) {
             println!("Deleting link at {base_absolute:?}");
-            fs::remove_file(base_absolute).wrap_err("Failed to delete symlink")?;
+            fs::remove_file(&base_absolute).wrap_err("Failed to delete symlink")?;
+            state.symlinks.shift_remove(&base_absolute);
         } else {
             println!(
                 "ERROR: {base_absolute:?} exists but points to {current_target:?} instead of {symlink_target:?}"
@@ -33,6 +58,160 @@ pub fn unlink(base_dir: &Path, group_dir: &Path) -> Result<()> {
         }
     }
 
+    // alternates are linked at their plain path
+    for relative_path in group_tree.paths() {
+        let Some((plain_path, _)) = plain_path_of_alternate(&relative_path) else {
+            continue;
+        };
+
+        let base_absolute = base_dir.join(plain_path);
+        let Some(current_target) =
+            read_node(&base_absolute)?.and_then(|node| node.target().map(Path::to_owned))
+        else {
+            continue;
+        };
+
+        let dotfile_absolute = group_dir.join(&relative_path);
+        let symlink_target = create_relative_symlink_target_path(&base_absolute, &dotfile_absolute);
+
+        if are_equivalent_symlink_targets(&base_absolute, &current_target, &symlink_target) {
+            println!("Deleting link at {base_absolute:?}");
+            fs::remove_file(&base_absolute).wrap_err("Failed to delete symlink")?;
+            state.symlinks.shift_remove(&base_absolute);
+        }
+    }
+
+    for (node, relative_path) in &group_tree {
+        if node.is_regular()
+            && let Some(output_relative_path) = template_output_path(&relative_path)
+        {
+            let output_absolute = base_dir.join(output_relative_path);
+            remove_rendered_template(
+                &group_dir.join(&relative_path),
+                &output_absolute,
+                &options.template_variables,
+            )?;
+            if !try_exists(&output_absolute)? {
+                state.rendered.shift_remove(&output_absolute);
+            }
+        }
+    }
+
+    remove_recorded_symlinks(group_dir, state)?;
+    remove_created_directories(state)?;
+
+    if options.restore_backups {
+        for relative_path in group_tree.paths() {
+            restore_backup(&base_dir.join(relative_path))?;
+        }
+    }
+
+    Ok(())
+}
+
+/// Deletes the links `state` says `link` created that still point into the group, like the ones
+/// left behind by files since removed from the group, and forgets the rest.
+fn remove_recorded_symlinks(group_dir: &Path, state: &mut GroupState) -> Result<()> {
+    let group_dir = normalize_path(group_dir);
+
+    for link in state.symlinks.clone() {
+        if try_exists(&link)? && read_file_type(&link)? == FileType::Symlink {
+            let target = fs::read_link(&link)?;
+            let parent = link.parent().unwrap_or(Path::new("/"));
+
+            if normalize_path(&parent.join(&target)).starts_with(&group_dir) {
+                println!("Deleting link at {link:?}");
+                fs::remove_file(&link).wrap_err("Failed to delete symlink")?;
+            } else {
+                println!("WARNING: {link:?} now points to {target:?}, leaving it");
+            }
+        }
+        state.symlinks.shift_remove(&link);
+    }
+
+    Ok(())
+}
+
+/// Deletes the directories `link` created, deepest first, if they're empty.
+fn remove_created_directories(state: &mut GroupState) -> Result<()> {
+    let mut directories = state.directories.iter().cloned().collect::<Vec<_>>();
+    directories.sort_by_key(|directory| Reverse(directory.components().count()));
+
+    for directory in directories {
+        if !try_exists(&directory)? || read_file_type(&directory)? != FileType::Directory {
+            state.directories.shift_remove(&directory);
+        } else if fs::read_dir(&directory)?.next().is_none() {
+            println!("Deleting directory at {directory:?}");
+            fs::remove_dir(&directory).wrap_err("Failed to delete directory")?;
+            state.directories.shift_remove(&directory);
+        }
+    }
+
+    Ok(())
+}
+
+/// Deletes the output of a template, if it's still what `link` rendered.
+fn remove_rendered_template(
+    template_path: &Path,
+    output_path: &Path,
+    variables: &TemplateVariables,
+) -> Result<()> {
+    if !try_exists(output_path)? {
+        return Ok(());
+    }
+
+    if read_file_type(output_path)? == FileType::Regular
+        && fs::read(output_path)? == render_file(template_path, variables)?.as_bytes()
+    {
+        println!("Deleting rendered template at {output_path:?}");
+        fs::remove_file(output_path).wrap_err("Failed to delete rendered template")?;
+    } else {
+        println!("ERROR: {output_path:?} differs from the rendered template, leaving it");
+    }
+
+    Ok(())
+}
+
+/// Moves the backup made by `link --backup` back to `path`, if nothing is there.
+fn restore_backup(path: &Path) -> Result<()> {
+    let Some(parent) = path.parent() else {
+        return Ok(());
+    };
+
+    if try_exists(path)? || !try_exists(parent)? || read_file_type(parent)? != FileType::Directory {
+        return Ok(());
+    }
+
+    // Unwrap Safety:
+    //   `path` has a parent, so it has a file name
+    let backup_prefix = utils::backup_path(path, "");
+    let backup_prefix = backup_prefix.file_name().unwrap().as_encoded_bytes();
+
+    let mut backups = vec![];
+    for entry in fs::read_dir(parent)? {
+        let entry = entry?;
+        if entry
+            .file_name()
+            .as_encoded_bytes()
+            .starts_with(backup_prefix)
+        {
+            backups.push(entry.path());
+        }
+    }
+
+    match backups.as_slice() {
+        [] => {}
+        [backup] => {
+            fs::rename(backup, path)?;
+            println!("Restored backup {backup:?} to {path:?}");
+        }
+        _ => {
+            println!(
+                "ERROR: found multiple backups for {path:?}, restore one manually: {backups:#?}"
+            );
+        }
+    }
+
     Ok(())
 }
 
@@ -42,7 +221,10 @@ mod tests {
     use pretty_assertions::assert_eq;
 
     use super::*;
-    use crate::utils::test_utils::cd_to_testdir;
+    use crate::{
+        commands::link::{LinkOptions, link},
+        utils::test_utils::cd_to_testdir,
+    };
 
     #[test]
     fn test_unlink() {
@@ -77,7 +259,13 @@ mod tests {
         home.write_structure_at(".").unwrap();
         dotfiles.write_structure_at(".").unwrap();
 
-        unlink(test_dir, &test_dir.join("dotfiles/i3")).unwrap();
+        unlink(
+            test_dir,
+            &test_dir.join("dotfiles/i3"),
+            &UnlinkOptions::default(),
+            &mut GroupState::default(),
+        )
+        .unwrap();
 
         let result = home.symlink_read_structure_at(".").unwrap();
         assert_eq!(result, expected_home);
@@ -113,9 +301,226 @@ mod tests {
         base.write_structure_at(".").unwrap();
         dotfiles.write_structure_at(".").unwrap();
 
-        unlink(&base_dir, &test_dir.join("dotfiles/sddm")).unwrap();
+        unlink(
+            &base_dir,
+            &test_dir.join("dotfiles/sddm"),
+            &UnlinkOptions::default(),
+            &mut GroupState::default(),
+        )
+        .unwrap();
 
         let result = base.symlink_read_structure_at(".").unwrap();
         assert_eq!(result, expected_base);
     }
+
+    #[test]
+    fn test_unlink_restore_backups() {
+        let (_dropper, test_dir) = cd_to_testdir().unwrap();
+
+        let home = tree! {
+            ".bashrc" -> "dotfiles/shell/.bashrc"
+            ".bashrc.dotin-backup-old"
+            ".profile" -> "dotfiles/shell/.profile"
+            ".profile.dotin-backup-1"
+            ".profile.dotin-backup-2"
+        };
+        let dotfiles = tree! {
+            dotfiles: [
+                shell: [
+                    ".bashrc"
+                    ".profile"
+                ]
+            ]
+        };
+        let expected_home = tree! {
+            ".bashrc"
+            ".profile.dotin-backup-1"
+            ".profile.dotin-backup-2"
+        };
+
+        home.write_structure_at(".").unwrap();
+        dotfiles.write_structure_at(".").unwrap();
+
+        let options = UnlinkOptions {
+            restore_backups: true,
+            ..UnlinkOptions::default()
+        };
+        unlink(
+            test_dir,
+            &test_dir.join("dotfiles/shell"),
+            &options,
+            &mut GroupState::default(),
+        )
+        .unwrap();
+
+        // `.profile` is ambiguous, so it's left for the user to restore
+        let mut result = FsTree::symlink_read_at(".").unwrap();
+        result.children_mut().unwrap().remove(Path::new("dotfiles"));
+        assert_eq!(result, expected_home);
+    }
+
+    #[test]
+    fn test_unlink_equivalent_targets() {
+        let (_dropper, test_dir) = cd_to_testdir().unwrap();
+        let absolute_target = test_dir.join("dotfiles/group/absolute");
+
+        // `dotfiles` is a symlink, and `through_real_path` was created pointing to the real path
+        let home = tree! {
+            dot_slash -> "./dotfiles/group/dot_slash"
+            absolute -> {absolute_target}
+            through_real_path -> "data/dotfiles/group/through_real_path"
+            foreign -> "data/dotfiles/other_group/foreign"
+            dotfiles -> "data/dotfiles"
+        };
+        let dotfiles = tree! {
+            data: [
+                dotfiles: [
+                    group: [
+                        dot_slash
+                        absolute
+                        through_real_path
+                        foreign
+                    ]
+                ]
+            ]
+        };
+        let expected_home = tree! {
+            foreign -> "data/dotfiles/other_group/foreign"
+            dotfiles -> "data/dotfiles"
+        };
+
+        home.write_structure_at(".").unwrap();
+        dotfiles.write_structure_at(".").unwrap();
+
+        unlink(
+            test_dir,
+            &test_dir.join("dotfiles/group"),
+            &UnlinkOptions::default(),
+            &mut GroupState::default(),
+        )
+        .unwrap();
+
+        let result = home.symlink_read_structure_at(".").unwrap();
+        assert_eq!(result, expected_home);
+    }
+
+    #[test]
+    fn test_unlink_alternates() {
+        let (_dropper, test_dir) = cd_to_testdir().unwrap();
+
+        let home = tree! {
+            ".Xresources" -> "dotfiles/x11/.Xresources##host.desk"
+        };
+        let dotfiles = tree! {
+            dotfiles: [
+                x11: [
+                    ".Xresources##default"
+                    ".Xresources##host.desk"
+                ]
+            ]
+        };
+        let expected_home = tree! {};
+
+        home.write_structure_at(".").unwrap();
+        dotfiles.write_structure_at(".").unwrap();
+
+        unlink(
+            test_dir,
+            &test_dir.join("dotfiles/x11"),
+            &UnlinkOptions::default(),
+            &mut GroupState::default(),
+        )
+        .unwrap();
+
+        let result = home.symlink_read_structure_at(".").unwrap();
+        assert_eq!(result, expected_home);
+    }
+
+    #[test]
+    fn test_unlink_removes_created_directories() {
+        let (_dropper, test_dir) = cd_to_testdir().unwrap();
+
+        let home = tree! {
+            ".config": []
+        };
+        let dotfiles = tree! {
+            dotfiles: [
+                i3: [
+                    ".config": [
+                        i3: [
+                            scripts: [
+                                "run.sh"
+                            ]
+                        ]
+                    ]
+                ]
+            ]
+        };
+
+        home.write_structure_at(".").unwrap();
+        dotfiles.write_structure_at(".").unwrap();
+
+        let mut state = GroupState::default();
+        link(
+            test_dir,
+            &test_dir.join("dotfiles/i3"),
+            &LinkOptions::default(),
+            &mut state,
+        )
+        .unwrap();
+        assert_eq!(state.directories.len(), 2);
+
+        unlink(
+            test_dir,
+            &test_dir.join("dotfiles/i3"),
+            &UnlinkOptions::default(),
+            &mut state,
+        )
+        .unwrap();
+
+        // `.config` existed before, so it's kept
+        let result = home.symlink_read_structure_at(".").unwrap();
+        assert_eq!(result, home);
+        assert!(!test_dir.join(".config/i3").exists());
+        assert!(state.is_empty());
+    }
+
+    #[test]
+    fn test_unlink_removes_recorded_links_of_removed_files() {
+        let (_dropper, test_dir) = cd_to_testdir().unwrap();
+
+        let dotfiles = tree! {
+            dotfiles: [
+                zsh: [
+                    ".zshrc"
+                    ".zshenv"
+                ]
+            ]
+        };
+        dotfiles.write_structure_at(".").unwrap();
+
+        let mut state = GroupState::default();
+        link(
+            test_dir,
+            &test_dir.join("dotfiles/zsh"),
+            &LinkOptions::default(),
+            &mut state,
+        )
+        .unwrap();
+
+        // removed from the group after being linked, the link is left dangling
+        fs::remove_file("dotfiles/zsh/.zshenv").unwrap();
+
+        unlink(
+            test_dir,
+            &test_dir.join("dotfiles/zsh"),
+            &UnlinkOptions::default(),
+            &mut state,
+        )
+        .unwrap();
+
+        assert!(!utils::try_exists(test_dir.join(".zshrc")).unwrap());
+        assert!(!utils::try_exists(test_dir.join(".zshenv")).unwrap());
+        assert!(state.is_empty());
+    }
 }
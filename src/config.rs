@@ -3,12 +3,12 @@ use std::{
     path::{Path, PathBuf},
 };
 
-use eyre::{bail, eyre};
+use eyre::{WrapErr, bail, eyre};
 use fs_err as fs;
 use indexmap::IndexMap;
 use serde::Deserialize;
 
-use crate::{Result, utils::try_exists};
+use crate::{Result, hooks::HOOK_NAMES, permissions::Permissions, utils::try_exists};
 
 const INITIAL_CONFIG: &str = indoc::indoc! { r#"
     # `dotin` configuration file
@@ -19,21 +19,75 @@ const INITIAL_CONFIG: &str = indoc::indoc! { r#"
     [override_base_folder]
     # sddm = "/etc"
     # systemd = "/etc"
+
+    # Variables for `*.dotin.tmpl` templates, used as `{{ name }}`
+    # (Note: `hostname`, `username` and `os_*` fields are set by default)
+    [variables]
+    # email = "me@example.com"
+
+    # Groups for `dotin import --auto`, by path relative to the base folder
+    # (Note: these extend the builtin catalog, and win over it)
+    [catalog]
+    # ".config/foot" = "terminal"
+
+    # Shell commands to run before or after link, unlink, import and discard of a group
+    # (Note: run `dotin --no-hooks ...` to skip them)
+    # [hooks.systemd-user]
+    # post-link = "systemctl --user daemon-reload"
+
+    # Modes for group files, applied by `dotin link` (git only keeps the executable bit)
+    # (Note: paths are relative to the group, `**` matches any subdirectories)
+    # [permissions.ssh]
+    # ".ssh/config" = "0600"
 "# };
 
 #[derive(Default, Deserialize)]
 pub struct Config {
     #[serde(default)]
     pub override_base_folder: IndexMap<String, String>,
+    #[serde(default)]
+    pub variables: IndexMap<String, String>,
+    #[serde(default)]
+    pub hooks: IndexMap<String, IndexMap<String, String>>,
+    #[serde(default)]
+    pub permissions: IndexMap<String, IndexMap<String, String>>,
+    #[serde(default)]
+    pub catalog: IndexMap<String, String>,
+    /// Base folder for every group, set for a single invocation by `--base`.
+    #[serde(skip)]
+    pub base_folder: Option<PathBuf>,
 }
 
 impl Config {
     pub fn base_folder_for_group<'a>(&'a self, home: &'a Path, group: &str) -> Cow<'a, Path> {
+        if let Some(base) = &self.base_folder {
+            return Cow::Borrowed(base);
+        }
+
         self.override_base_folder
             .get(group)
             .map(|base| Cow::Borrowed(Path::new(base)))
             .unwrap_or(Cow::Borrowed(home))
     }
+
+    /// Checks if the group has `override_base_folder`, `hooks` or `permissions` entries.
+    pub fn has_entries_for_group(&self, group: &str) -> bool {
+        self.override_base_folder.contains_key(group)
+            || self.hooks.contains_key(group)
+            || self.permissions.contains_key(group)
+            || self
+                .catalog
+                .values()
+                .any(|catalog_group| catalog_group == group)
+    }
+
+    pub fn permissions_for_group(&self, group: &str) -> Result<Permissions> {
+        match self.permissions.get(group) {
+            Some(declared) => Permissions::parse(declared)
+                .wrap_err_with(|| format!("in config permissions for group {group:?}")),
+            None => Ok(Permissions::default()),
+        }
+    }
 }
 
 #[derive(Default)]
@@ -86,9 +140,339 @@ fn validate_config(config: &Config) -> Result<()> {
         }
     }
 
+    for (group, hooks) in &config.hooks {
+        for hook in hooks.keys() {
+            if !HOOK_NAMES.contains(&hook.as_str()) {
+                return Err(eyre!(
+                    "config hooks for group {group:?} has unknown hook {hook:?}; expected one of {}",
+                    HOOK_NAMES.join(", "),
+                ));
+            }
+        }
+    }
+
+    for group in config.permissions.keys() {
+        config.permissions_for_group(group)?;
+    }
+
+    Ok(())
+}
+
+/// Saves `override_base_folder.<group> = base` into the config file, creating it if needed.
+pub fn save_override_base_folder(
+    home: &Path,
+    config: &ConfigWithPath,
+    group: &str,
+    base: &Path,
+) -> Result<()> {
+    let Some(base) = base.to_str() else {
+        bail!("can't save base folder {base:?} in the config, it isn't valid UTF-8");
+    };
+
+    if config
+        .inner
+        .override_base_folder
+        .get(group)
+        .map(String::as_str)
+        == Some(base)
+    {
+        return Ok(());
+    }
+
+    let (path, contents) = match &config.path {
+        Some(path) => (path.clone(), fs::read_to_string(path)?),
+        None => (
+            home.join(".config/dotin/config.toml"),
+            INITIAL_CONFIG.to_owned(),
+        ),
+    };
+
+    let contents = set_override_base_folder(&contents, group, base);
+    validate_config(&toml::from_str(&contents)?)?;
+
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    fs::write(&path, contents)?;
+    println!("Saved base folder {base:?} for group {group:?} in {path:?}");
+
+    Ok(())
+}
+
+/// Removes the group entries (`override_base_folder`, `hooks`, `permissions`, and the `catalog`
+/// paths mapped to it) from the config file, if it has any.
+pub fn remove_group_from_config(config: &ConfigWithPath, group: &str) -> Result<()> {
+    edit_config_file(
+        config,
+        |contents| remove_group_entries(contents, group),
+        &format!("Removed entries of group {group:?}"),
+    )
+}
+
+/// Renames the group entries (`override_base_folder`, `hooks`, `permissions`, and the group of
+/// `catalog` paths) in the config file, if it has any.
+pub fn rename_group_in_config(config: &ConfigWithPath, old: &str, new: &str) -> Result<()> {
+    if config.inner.has_entries_for_group(new) {
+        bail!("the config already has entries for group {new:?}");
+    }
+
+    edit_config_file(
+        config,
+        |contents| rename_group_entries(contents, old, new),
+        &format!("Renamed entries of group {old:?} to {new:?}"),
+    )
+}
+
+/// Edits the config file text (if there's a config file), validating the result before writing
+/// it.
+/// Drops the `override_base_folder` of the merged `source` group (both share the base folder), and
+/// moves its `catalog` paths to `destination`, in the config file, if it has any.
+pub fn merge_group_in_config(
+    config: &ConfigWithPath,
+    source: &str,
+    destination: &str,
+) -> Result<()> {
+    edit_config_file(
+        config,
+        |contents| merge_group_entries(contents, source, destination),
+        &format!("Moved entries of group {source:?} to {destination:?}"),
+    )
+}
+
+fn edit_config_file(
+    config: &ConfigWithPath,
+    edit: impl FnOnce(&str) -> String,
+    message: &str,
+) -> Result<()> {
+    let Some(path) = &config.path else {
+        return Ok(());
+    };
+
+    let contents = fs::read_to_string(path)?;
+    let edited = edit(&contents);
+    if edited == contents {
+        return Ok(());
+    }
+
+    validate_config(&toml::from_str(&edited)?)?;
+    fs::write(path, edited)?;
+    println!("{message} in {path:?}");
+
     Ok(())
 }
 
+/// Sets the key in the `[override_base_folder]` section of the config contents, keeping the rest
+/// (and comments) as is.
+fn set_override_base_folder(contents: &str, group: &str, base: &str) -> String {
+    let key = toml_key(group);
+    let entry = format!("{key} = {}", toml::Value::String(base.to_owned()));
+
+    let mut lines: Vec<String> = contents.lines().map(str::to_owned).collect();
+
+    let Some((header, section_end)) = section_range(&lines, "override_base_folder") else {
+        let separator = if contents.is_empty() || contents.ends_with("\n\n") {
+            ""
+        } else if contents.ends_with('\n') {
+            "\n"
+        } else {
+            "\n\n"
+        };
+        return format!("{contents}{separator}[override_base_folder]\n{entry}\n");
+    };
+
+    let existing = lines[header + 1..section_end]
+        .iter()
+        .position(|line| line_key(line).as_deref() == Some(group));
+
+    match existing {
+        Some(position) => lines[header + 1 + position] = entry,
+        None => lines.insert(header + 1, entry),
+    }
+
+    lines.join("\n") + "\n"
+}
+
+/// Removes the group key of `[override_base_folder]`, the `[catalog]` paths mapped to the group,
+/// and the `[hooks.<group>]` and `[permissions.<group>]` sections, keeping the rest (and
+/// comments) as is.
+fn remove_group_entries(contents: &str, group: &str) -> String {
+    let mut lines: Vec<String> = contents.lines().map(str::to_owned).collect();
+
+    for table in ["hooks", "permissions"] {
+        let header = format!("{table}.{}", toml_key(group));
+        if let Some((mut start, end)) = section_range(&lines, &header) {
+            lines.drain(start..end);
+
+            // don't leave two blank lines where the section was
+            while start > 0
+                && lines[start - 1].trim().is_empty()
+                && lines.get(start).is_none_or(|line| line.trim().is_empty())
+            {
+                lines.remove(start - 1);
+                start -= 1;
+            }
+        }
+    }
+
+    remove_override_base_folder(&mut lines, group);
+
+    // so `import --auto` doesn't bring the group back
+    if let Some((header, section_end)) = section_range(&lines, "catalog") {
+        let mut index = header + 1;
+        for _ in header + 1..section_end {
+            if line_value(&lines[index]).as_deref() == Some(group) {
+                lines.remove(index);
+            } else {
+                index += 1;
+            }
+        }
+    }
+
+    if lines.is_empty() {
+        return String::new();
+    }
+    lines.join("\n") + "\n"
+}
+
+/// Renames the group key of `[override_base_folder]`, the `[hooks.<group>]` and
+/// `[permissions.<group>]` headers, and the group of `[catalog]` paths, keeping the rest (and
+/// comments) as is.
+fn rename_group_entries(contents: &str, old: &str, new: &str) -> String {
+    let mut lines: Vec<String> = contents.lines().map(str::to_owned).collect();
+
+    for table in ["hooks", "permissions"] {
+        let header = format!("{table}.{}", toml_key(old));
+        if let Some((start, _)) = section_range(&lines, &header) {
+            lines[start] = format!("[{table}.{}]", toml_key(new));
+        }
+    }
+
+    if let Some((header, section_end)) = section_range(&lines, "override_base_folder") {
+        for line in &mut lines[header + 1..section_end] {
+            if line_key(line).as_deref() == Some(old)
+                && let Some((_, value)) = line.split_once('=')
+            {
+                *line = format!("{} ={value}", toml_key(new));
+            }
+        }
+    }
+
+    rename_catalog_group(&mut lines, old, new);
+
+    if lines.is_empty() {
+        return String::new();
+    }
+    lines.join("\n") + "\n"
+}
+
+/// Removes the group key of `[override_base_folder]`, and moves the `[catalog]` paths of the
+/// `source` group to `destination`, keeping the rest (and comments) as is.
+///
+/// Hooks and permissions are left for the user to move, they might not fit the other group.
+fn merge_group_entries(contents: &str, source: &str, destination: &str) -> String {
+    let mut lines: Vec<String> = contents.lines().map(str::to_owned).collect();
+
+    remove_override_base_folder(&mut lines, source);
+    rename_catalog_group(&mut lines, source, destination);
+
+    if lines.is_empty() {
+        return String::new();
+    }
+    lines.join("\n") + "\n"
+}
+
+fn remove_override_base_folder(lines: &mut Vec<String>, group: &str) {
+    if let Some((header, section_end)) = section_range(lines, "override_base_folder")
+        && let Some(position) = lines[header + 1..section_end]
+            .iter()
+            .position(|line| line_key(line).as_deref() == Some(group))
+    {
+        lines.remove(header + 1 + position);
+    }
+}
+
+/// Maps the `[catalog]` paths of the `old` group to `new`.
+fn rename_catalog_group(lines: &mut [String], old: &str, new: &str) {
+    let Some((header, section_end)) = section_range(lines, "catalog") else {
+        return;
+    };
+
+    let (old_value, new_value) = (
+        toml::Value::String(old.to_owned()).to_string(),
+        toml::Value::String(new.to_owned()).to_string(),
+    );
+
+    for line in &mut lines[header + 1..section_end] {
+        if line_value(line).as_deref() == Some(old)
+            && let Some((key, value)) = line.split_once('=')
+        {
+            let value = match value.contains(&old_value) {
+                true => value.replacen(&old_value, &new_value, 1),
+                false => format!(" {new_value}"),
+            };
+            *line = format!("{key}={value}");
+        }
+    }
+}
+
+/// The line range of the `[header]` section, from the header up to the next one, leaving out the
+/// comments and blank lines right before the next header (they're likely about it).
+fn section_range(lines: &[String], header: &str) -> Option<(usize, usize)> {
+    let start = lines
+        .iter()
+        .position(|line| line.trim() == format!("[{header}]"))?;
+
+    let mut end = lines[start + 1..]
+        .iter()
+        .position(|line| line.trim_start().starts_with('['))
+        .map_or(lines.len(), |position| start + 1 + position);
+
+    if end < lines.len() {
+        while end > start + 1 && {
+            let line = lines[end - 1].trim();
+            line.is_empty() || line.starts_with('#')
+        } {
+            end -= 1;
+        }
+    }
+
+    Some((start, end))
+}
+
+/// The key of a `key = value` line, unquoted.
+fn line_key(line: &str) -> Option<String> {
+    let (key, _) = line.split_once('=')?;
+    let key = key.trim();
+    if key.starts_with('#') {
+        return None;
+    }
+
+    let unquoted = key
+        .strip_prefix('"')
+        .and_then(|key| key.strip_suffix('"'))
+        .unwrap_or(key);
+    Some(unquoted.to_owned())
+}
+
+/// The string value of a `key = "value"` line.
+fn line_value(line: &str) -> Option<String> {
+    let table = toml::from_str::<toml::Table>(line).ok()?;
+    table.values().next()?.as_str().map(str::to_owned)
+}
+
+/// Quotes the key if it isn't a TOML bare key.
+fn toml_key(key: &str) -> String {
+    let is_bare_key = key
+        .chars()
+        .all(|ch| ch.is_ascii_alphanumeric() || ch == '-' || ch == '_');
+
+    if is_bare_key {
+        key.to_owned()
+    } else {
+        toml::Value::String(key.to_owned()).to_string()
+    }
+}
+
 pub fn init_config(home: &Path, dotfiles: &Path) -> Result<()> {
     let existing = read_config(home, dotfiles)?;
     if let Some(path) = existing.path {
@@ -146,4 +530,178 @@ mod tests {
 
         assert!(error.contains("has relative path"), "msg = {error}");
     }
+
+    #[test]
+    fn rejects_unknown_hook() {
+        let config: Config = toml::from_str(indoc::indoc! { r#"
+            [hooks.fonts]
+            post-link = "fc-cache"
+            after-link = "fc-cache"
+        "# })
+        .unwrap();
+
+        let error = validate_config(&config).unwrap_err().to_string();
+
+        assert!(
+            error.contains("unknown hook \"after-link\""),
+            "msg = {error}"
+        );
+    }
+
+    #[test]
+    fn catalog_paths_are_group_entries() {
+        let config: Config = toml::from_str(indoc::indoc! { r#"
+            [catalog]
+            ".config/sway" = "wm"
+        "# })
+        .unwrap();
+
+        assert!(config.has_entries_for_group("wm"));
+        assert!(!config.has_entries_for_group("sway"));
+    }
+
+    #[test]
+    fn rejects_invalid_permissions_mode() {
+        let config: Config = toml::from_str(indoc::indoc! { r#"
+            [permissions.ssh]
+            ".ssh/config" = "rw-------"
+        "# })
+        .unwrap();
+
+        let error = format!("{:?}", validate_config(&config).unwrap_err());
+
+        assert!(error.contains("invalid mode"), "msg = {error}");
+    }
+
+    #[test]
+    fn sets_override_base_folder() {
+        let with_section = indoc::indoc! { r#"
+            # comment
+            [override_base_folder]
+            sddm = "/etc"
+
+            [variables]
+        "# };
+
+        assert_eq!(
+            set_override_base_folder(with_section, "nginx", "/etc/nginx"),
+            indoc::indoc! { r#"
+                # comment
+                [override_base_folder]
+                nginx = "/etc/nginx"
+                sddm = "/etc"
+
+                [variables]
+            "# },
+        );
+        assert_eq!(
+            set_override_base_folder(with_section, "sddm", "/usr/share"),
+            with_section.replace(r#"sddm = "/etc""#, r#"sddm = "/usr/share""#),
+        );
+        assert_eq!(
+            set_override_base_folder("[variables]\n", "my group", "/etc"),
+            "[variables]\n\n[override_base_folder]\n\"my group\" = \"/etc\"\n",
+        );
+    }
+
+    #[test]
+    fn removes_group_entries() {
+        let contents = indoc::indoc! { r#"
+            [override_base_folder]
+            sddm = "/etc"
+            "nginx" = "/etc/nginx"
+
+            [hooks.nginx]
+            post-link = "systemctl reload nginx"
+
+            # about the next section
+            [hooks.sddm]
+            post-link = "true"
+
+            [permissions.nginx]
+            "nginx.conf" = "0600"
+
+            [catalog]
+            ".config/nginx" = "nginx"
+            ".config/sddm" = "sddm"
+            "nginx.conf" = "nginx" # system-wide
+        "# };
+
+        assert_eq!(
+            remove_group_entries(contents, "nginx"),
+            indoc::indoc! { r#"
+                [override_base_folder]
+                sddm = "/etc"
+
+                # about the next section
+                [hooks.sddm]
+                post-link = "true"
+
+                [catalog]
+                ".config/sddm" = "sddm"
+            "# },
+        );
+        assert_eq!(remove_group_entries(contents, "other"), contents);
+    }
+
+    #[test]
+    fn merges_group_entries() {
+        let contents = indoc::indoc! { r#"
+            [override_base_folder]
+            rofi = "/home/me"
+            desktop = "/home/me"
+
+            [hooks.rofi]
+            post-link = "true"
+
+            [catalog]
+            ".config/rofi" = "rofi"
+        "# };
+
+        assert_eq!(
+            merge_group_entries(contents, "rofi", "desktop"),
+            indoc::indoc! { r#"
+                [override_base_folder]
+                desktop = "/home/me"
+
+                [hooks.rofi]
+                post-link = "true"
+
+                [catalog]
+                ".config/rofi" = "desktop"
+            "# },
+        );
+    }
+
+    #[test]
+    fn renames_group_entries() {
+        let contents = indoc::indoc! { r#"
+            [override_base_folder]
+            wm = "/etc" # system-wide
+
+            [hooks.wm]
+            post-link = "i3-msg reload"
+
+            [catalog]
+            ".config/i3" = "wm" # tiling
+            ".config/sway" = 'wm'
+            ".config/wmctl" = "wmctl"
+        "# };
+
+        assert_eq!(
+            rename_group_entries(contents, "wm", "i3"),
+            indoc::indoc! { r#"
+                [override_base_folder]
+                i3 = "/etc" # system-wide
+
+                [hooks.i3]
+                post-link = "i3-msg reload"
+
+                [catalog]
+                ".config/i3" = "i3" # tiling
+                ".config/sway" = "i3"
+                ".config/wmctl" = "wmctl"
+            "# },
+        );
+    }
 }
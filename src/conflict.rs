@@ -0,0 +1,333 @@
+use std::path::{self, Path, PathBuf};
+
+use eyre::{bail, eyre};
+use fs_err as fs;
+
+use crate::{
+    Result,
+    interactive::{ConflictChoice, ask_conflict_choice},
+    utils::{
+        self, FileType, are_equivalent_symlink_targets, create_relative_symlink_target_path,
+        default_backup_suffix, files_match_content, read_file_type, rewrite_symlink_target,
+        try_exists,
+    },
+};
+
+/// The command moving files, which decides the wording, and which side is the group one.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Operation {
+    /// From the base folder into a group, symlinks get their targets rewritten.
+    Import,
+    /// From a group back to the base folder.
+    Discard,
+    /// From a group to another.
+    Move,
+}
+
+impl Operation {
+    fn verb(self) -> &'static str {
+        match self {
+            Operation::Import => "import",
+            Operation::Discard => "discard",
+            Operation::Move => "move",
+        }
+    }
+}
+
+/// Which conflicts are resolved without asking, the rest fail (or are asked, if interactive).
+#[derive(Debug, Clone, Copy)]
+pub struct ConflictPolicy {
+    /// Accept a destination with the same content as the source (or the same symlink target).
+    pub accept_identical: bool,
+    /// Replace empty files and directories at the destination.
+    pub replace_empty: bool,
+    /// Replace a symlink at the destination pointing to the source (like one made by `link`).
+    pub replace_links_to_source: bool,
+    /// Ask how to resolve the other conflicts instead of failing.
+    pub interactive: bool,
+}
+
+impl Default for ConflictPolicy {
+    fn default() -> Self {
+        Self {
+            accept_identical: true,
+            replace_empty: true,
+            replace_links_to_source: true,
+            interactive: false,
+        }
+    }
+}
+
+/// How to resolve a conflict between the path being moved (the source) and what is at its
+/// destination.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ConflictResolution {
+    /// Nothing at the destination.
+    None,
+    /// The destination is empty, or a link to the source, delete it, then move.
+    DeleteDestination,
+    /// The destination already matches the source, there's nothing to move.
+    Identical,
+    /// Leave both as they are.
+    Skip,
+    /// Keep the destination version, delete the source.
+    DeleteSource,
+    /// Keep the source version, delete the destination.
+    ReplaceDestination,
+    /// Keep both, moving the destination version to this path (outside of the group).
+    RenameDestination(PathBuf),
+}
+
+impl ConflictResolution {
+    /// Clears the way for moving `source` to `destination`, returns `false` if it shouldn't be
+    /// moved.
+    pub fn prepare(&self, source: &Path, destination: &Path) -> Result<bool> {
+        match self {
+            ConflictResolution::None => {}
+            ConflictResolution::DeleteDestination | ConflictResolution::ReplaceDestination => {
+                utils::remove_path(destination)?;
+            }
+            ConflictResolution::RenameDestination(renamed_path) => {
+                utils::cheap_move_with_fallback(destination, renamed_path)?;
+            }
+            ConflictResolution::Identical | ConflictResolution::Skip => return Ok(false),
+            ConflictResolution::DeleteSource => {
+                utils::remove_path(source)?;
+                return Ok(false);
+            }
+        }
+        Ok(true)
+    }
+}
+
+/// Checks what is at `destination` before moving `source` there, asking the user about
+/// conflicts the policy doesn't resolve, if interactive.
+pub fn resolve_conflict(
+    source: &Path,
+    destination: &Path,
+    operation: Operation,
+    policy: &ConflictPolicy,
+) -> Result<ConflictResolution> {
+    match check_conflict(source, destination, operation, policy) {
+        Err(err) if policy.interactive && operation != Operation::Move => {
+            println!("{err:#}");
+            ask_conflict_resolution(source, destination, operation)
+        }
+        result => result,
+    }
+}
+
+fn check_conflict(
+    source: &Path,
+    destination: &Path,
+    operation: Operation,
+    policy: &ConflictPolicy,
+) -> Result<ConflictResolution> {
+    if !try_exists(destination)? {
+        return Ok(ConflictResolution::None);
+    }
+
+    let verb = operation.verb();
+    let (source_type, destination_type) = (read_file_type(source)?, read_file_type(destination)?);
+
+    use FileType::*;
+    match (source_type, destination_type) {
+        (_, Symlink) if policy.replace_links_to_source && is_link_to(destination, source)? => {
+            Ok(ConflictResolution::DeleteDestination)
+        }
+        (_, Regular) if policy.replace_empty && fs::symlink_metadata(destination)?.len() == 0 => {
+            Ok(ConflictResolution::DeleteDestination)
+        }
+        (_, Directory) if fs::read_dir(destination)?.next().is_none() => {
+            if policy.replace_empty {
+                Ok(ConflictResolution::DeleteDestination)
+            } else {
+                Err(eyre!(
+                    "can't {verb} {source:?}, there is an empty directory at {destination:?}"
+                ))
+            }
+        }
+        (_, Directory) => Err(eyre!(
+            "can't {verb} {source:?}, there is a non-empty directory at {destination:?}"
+        )),
+        (Regular, Regular) => {
+            if !files_match_content(source, destination)? {
+                bail!(
+                    "can't {verb} {source:?}, it conflicts with {destination:?}, and their \
+                     content is different"
+                );
+            }
+            if !policy.accept_identical {
+                bail!("can't {verb} {source:?}, the same file is already at {destination:?}");
+            }
+            Ok(ConflictResolution::Identical)
+        }
+        (Symlink, Symlink) => {
+            if !symlinks_match_target(source, destination, operation)? {
+                bail!(
+                    "can't {verb} {source:?}, it conflicts with {destination:?}, they're both \
+                     symlinks but their targets are different"
+                );
+            }
+            if !policy.accept_identical {
+                bail!("can't {verb} {source:?}, the same symlink is already at {destination:?}");
+            }
+            Ok(ConflictResolution::Identical)
+        }
+        (Regular | Directory, Symlink) => Err(eyre!(
+            "can't {verb} {source:?}, there is a symlink at {destination:?}, but it points to \
+             {:?} and not to {source:?}",
+            fs::read_link(destination)?,
+        )),
+        (Directory | Symlink, Regular) => Err(eyre!(
+            "can't {verb} {source:?}, it conflicts with {destination:?}, and their types are \
+             different ({source_type} and {destination_type})",
+        )),
+    }
+}
+
+/// Checks if the symlink at `link` points to `path`.
+fn is_link_to(link: &Path, path: &Path) -> Result<bool> {
+    let link = path::absolute(link)?;
+    let expected_target = create_relative_symlink_target_path(&link, &path::absolute(path)?);
+    Ok(are_equivalent_symlink_targets(
+        &link,
+        &fs::read_link(&link)?,
+        &expected_target,
+    ))
+}
+
+/// Checks if the `source` symlink, once moved, points to the same path as `destination`.
+fn symlinks_match_target(source: &Path, destination: &Path, operation: Operation) -> Result<bool> {
+    let source_target = fs::read_link(source)?;
+    let moved_target = match operation {
+        Operation::Import => {
+            rewrite_symlink_target(&source_target, &path::absolute(source)?, destination)
+        }
+        Operation::Discard | Operation::Move => source_target,
+    };
+
+    Ok(are_equivalent_symlink_targets(
+        destination,
+        &moved_target,
+        &fs::read_link(destination)?,
+    ))
+}
+
+fn ask_conflict_resolution(
+    source: &Path,
+    destination: &Path,
+    operation: Operation,
+) -> Result<ConflictResolution> {
+    // the group version is the destination when importing, the source when discarding, and
+    // when renamed, it's moved next to the home path, so it isn't linked and committed later
+    let (group_path, home_path) = match operation {
+        Operation::Import => (destination, source),
+        Operation::Discard | Operation::Move => (source, destination),
+    };
+    let renamed_path = utils::backup_path(home_path, &default_backup_suffix());
+    let keep_source = match ask_conflict_choice(group_path, home_path, &renamed_path)? {
+        ConflictChoice::KeepGroup => operation != Operation::Import,
+        ConflictChoice::KeepHome => operation == Operation::Import,
+        ConflictChoice::Rename => {
+            if try_exists(&renamed_path)? {
+                bail!("can't move {destination:?} aside, {renamed_path:?} already exists");
+            }
+            return Ok(ConflictResolution::RenameDestination(renamed_path));
+        }
+        ConflictChoice::Skip => return Ok(ConflictResolution::Skip),
+    };
+
+    Ok(match keep_source {
+        true => ConflictResolution::ReplaceDestination,
+        false => ConflictResolution::DeleteSource,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use fs_tree::tree;
+    use pretty_assertions::assert_eq;
+
+    use super::*;
+    use crate::utils::test_utils::cd_to_testdir;
+
+    #[test]
+    fn test_conflict_matrix() {
+        let (_dropper, _test_dir) = cd_to_testdir().unwrap();
+
+        tree! {
+            file
+            empty
+            same
+            different
+            dir: [
+                inner
+            ]
+            empty_dir: []
+            link -> target
+            same_link -> target
+            link_to_file -> file
+        }
+        .write_structure_at(".")
+        .unwrap();
+        for (path, content) in [("file", "a"), ("same", "a"), ("different", "b")] {
+            fs::write(path, content).unwrap();
+        }
+
+        let policy = ConflictPolicy::default();
+        let check = |source: &str, destination: &str| {
+            check_conflict(
+                Path::new(source),
+                Path::new(destination),
+                Operation::Discard,
+                &policy,
+            )
+            .map_err(|err| err.to_string())
+        };
+
+        assert_eq!(check("file", "missing"), Ok(ConflictResolution::None));
+        assert_eq!(
+            check("dir", "empty"),
+            Ok(ConflictResolution::DeleteDestination)
+        );
+        assert_eq!(
+            check("link", "empty_dir"),
+            Ok(ConflictResolution::DeleteDestination)
+        );
+        assert_eq!(
+            check("file", "link_to_file"),
+            Ok(ConflictResolution::DeleteDestination)
+        );
+        assert_eq!(check("file", "same"), Ok(ConflictResolution::Identical));
+        assert_eq!(
+            check("link", "same_link"),
+            Ok(ConflictResolution::Identical)
+        );
+
+        let error = |source, destination| check(source, destination).unwrap_err();
+        assert!(error("file", "different").contains("content is different"));
+        assert!(error("file", "dir").contains("non-empty directory"));
+        assert!(error("dir", "link").contains("there is a symlink at"));
+        assert!(error("link", "file").contains("their types are different"));
+        assert!(error("link", "link_to_file").contains("targets are different"));
+
+        let strict = ConflictPolicy {
+            accept_identical: false,
+            replace_empty: false,
+            replace_links_to_source: false,
+            interactive: false,
+        };
+        let check_strict = |source: &str, destination: &str| {
+            check_conflict(
+                Path::new(source),
+                Path::new(destination),
+                Operation::Import,
+                &strict,
+            )
+        };
+        assert!(check_strict("file", "same").is_err());
+        assert!(check_strict("file", "empty").is_err());
+        assert!(check_strict("file", "empty_dir").is_err());
+    }
+}
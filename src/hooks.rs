@@ -0,0 +1,160 @@
+use std::{
+    path::{Path, PathBuf},
+    process,
+};
+
+use eyre::{WrapErr, bail};
+
+use crate::{Result, config::Config};
+
+/// Hook names accepted in the `[hooks.<group>]` config sections.
+pub const HOOK_NAMES: &[&str] = &[
+    "pre-link",
+    "post-link",
+    "pre-unlink",
+    "post-unlink",
+    "pre-import",
+    "post-import",
+    "pre-discard",
+    "post-discard",
+];
+
+/// What a hook is told through its environment variables.
+#[derive(Debug)]
+pub struct HookContext<'a> {
+    pub group: &'a str,
+    pub base_folder: &'a Path,
+    pub group_folder: &'a Path,
+    /// Paths changed by the command, for `pre-*` hooks, the ones about to be changed (if known).
+    pub changed_paths: &'a [PathBuf],
+}
+
+/// Runs the group hooks declared in the config.
+#[derive(Clone, Copy)]
+pub struct Hooks<'a> {
+    config: &'a Config,
+    enabled: bool,
+}
+
+impl<'a> Hooks<'a> {
+    pub fn new(config: &'a Config, enabled: bool) -> Self {
+        Self { config, enabled }
+    }
+
+    /// Runs `hook` (like `"post-link"`) for the group, if declared, with `sh -c` inside of the
+    /// group folder, fails if the hook fails.
+    pub fn run(&self, hook: &str, context: &HookContext) -> Result<()> {
+        debug_assert!(HOOK_NAMES.contains(&hook), "unknown hook {hook:?}");
+
+        let Some(command) = self
+            .config
+            .hooks
+            .get(context.group)
+            .and_then(|hooks| hooks.get(hook))
+        else {
+            return Ok(());
+        };
+
+        if !self.enabled {
+            println!("Skipping {hook} hook of group {:?}", context.group);
+            return Ok(());
+        }
+
+        let changed_paths = context
+            .changed_paths
+            .iter()
+            .map(|path| path.to_string_lossy())
+            .collect::<Vec<_>>()
+            .join("\n");
+
+        println!(
+            "Running {hook} hook of group {:?}: {command}",
+            context.group
+        );
+        let status = process::Command::new("sh")
+            .arg("-c")
+            .arg(command)
+            .current_dir(context.group_folder)
+            .env("DOTIN_HOOK", hook)
+            .env("DOTIN_GROUP", context.group)
+            .env("DOTIN_BASE_FOLDER", context.base_folder)
+            .env("DOTIN_GROUP_FOLDER", context.group_folder)
+            .env("DOTIN_CHANGED_PATHS", changed_paths)
+            .status()
+            .wrap_err_with(|| format!("Failed to run {hook} hook of group {:?}", context.group))?;
+
+        if !status.success() {
+            bail!("{hook} hook of group {:?} failed ({status})", context.group);
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use fs_err as fs;
+    use pretty_assertions::assert_eq;
+
+    use super::*;
+    use crate::utils::test_utils::cd_to_testdir;
+
+    fn config_with_hook(hook: &str, command: &str) -> Config {
+        let mut config = Config::default();
+        config
+            .hooks
+            .entry("group".to_owned())
+            .or_default()
+            .insert(hook.to_owned(), command.to_owned());
+        config
+    }
+
+    #[test]
+    fn test_run_hook_with_environment() {
+        let (_dropper, test_dir) = cd_to_testdir().unwrap();
+
+        let config = config_with_hook(
+            "post-link",
+            "echo \"$DOTIN_HOOK $DOTIN_GROUP $DOTIN_CHANGED_PATHS\" > output",
+        );
+        let context = HookContext {
+            group: "group",
+            base_folder: test_dir,
+            group_folder: test_dir,
+            changed_paths: &["a".into(), "b".into()],
+        };
+
+        Hooks::new(&config, true).run("pre-link", &context).unwrap();
+        assert!(!test_dir.join("output").exists());
+
+        Hooks::new(&config, false)
+            .run("post-link", &context)
+            .unwrap();
+        assert!(!test_dir.join("output").exists());
+
+        Hooks::new(&config, true)
+            .run("post-link", &context)
+            .unwrap();
+        let output = fs::read_to_string(test_dir.join("output")).unwrap();
+        assert_eq!(output, "post-link group a\nb\n");
+    }
+
+    #[test]
+    fn test_failing_hook_errs() {
+        let (_dropper, test_dir) = cd_to_testdir().unwrap();
+
+        let config = config_with_hook("pre-unlink", "exit 3");
+        let context = HookContext {
+            group: "group",
+            base_folder: test_dir,
+            group_folder: test_dir,
+            changed_paths: &[],
+        };
+
+        let error = Hooks::new(&config, true)
+            .run("pre-unlink", &context)
+            .unwrap_err()
+            .to_string();
+        assert!(error.contains("pre-unlink hook of group"), "msg = {error}");
+    }
+}
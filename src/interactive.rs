@@ -0,0 +1,152 @@
+use std::{
+    io::{self, BufRead, Write},
+    path::Path,
+    time::SystemTime,
+};
+
+use eyre::bail;
+use fs_err as fs;
+use similar::TextDiff;
+
+use crate::{
+    Result,
+    utils::{FileType, read_file_type},
+};
+
+/// How the user chose to resolve a conflict between the group and the home versions of a file.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ConflictChoice {
+    KeepGroup,
+    KeepHome,
+    /// Keep both, moving the version at the destination aside.
+    Rename,
+    Skip,
+}
+
+impl ConflictChoice {
+    fn parse(answer: &str) -> Option<Self> {
+        match answer.trim() {
+            "g" | "group" => Some(Self::KeepGroup),
+            "h" | "home" => Some(Self::KeepHome),
+            "r" | "rename" => Some(Self::Rename),
+            "s" | "skip" => Some(Self::Skip),
+            _ => None,
+        }
+    }
+}
+
+/// Shows both sides of a conflict and asks how to resolve it, `renamed_path` is where the
+/// destination version goes if the user chooses to rename it.
+pub fn ask_conflict_choice(
+    group_path: &Path,
+    home_path: &Path,
+    renamed_path: &Path,
+) -> Result<ConflictChoice> {
+    println!("Conflict between:");
+    println!("  group: {group_path:?} ({})", describe(group_path)?);
+    println!("  home:  {home_path:?} ({})", describe(home_path)?);
+
+    if let Some(diff) = text_diff(home_path, group_path)? {
+        print!("{diff}");
+    }
+
+    println!("  [g] keep the group version");
+    println!("  [h] keep the home version");
+    println!("  [r] keep both, moving the destination version to {renamed_path:?}");
+    println!("  [s] skip this file");
+
+    let mut stdin = io::stdin().lock();
+    loop {
+        print!("Choice [g/h/r/s]: ");
+        io::stdout().flush()?;
+
+        let mut answer = String::new();
+        if stdin.read_line(&mut answer)? == 0 {
+            bail!("no answer given for the conflict at {home_path:?}");
+        }
+
+        match ConflictChoice::parse(&answer) {
+            Some(choice) => return Ok(choice),
+            None => println!("Invalid choice {:?}", answer.trim()),
+        }
+    }
+}
+
+/// Type, size and age of the file at `path`.
+fn describe(path: &Path) -> Result<String> {
+    let file_type = read_file_type(path)?;
+    let metadata = fs::symlink_metadata(path)?;
+
+    let age = SystemTime::now()
+        .duration_since(metadata.modified()?)
+        .unwrap_or_default()
+        .as_secs();
+    let age = match age {
+        ..60 => format!("{age}s"),
+        60..3600 => format!("{}min", age / 60),
+        3600..86400 => format!("{}h", age / 3600),
+        _ => format!("{} days", age / 86400),
+    };
+
+    Ok(format!(
+        "{file_type}, {} bytes, modified {age} ago",
+        metadata.len()
+    ))
+}
+
+/// Unified diff from `old` to `new`, if both are text files.
+fn text_diff(old: &Path, new: &Path) -> Result<Option<String>> {
+    if read_file_type(old)? != FileType::Regular || read_file_type(new)? != FileType::Regular {
+        return Ok(None);
+    }
+
+    let (Ok(old_text), Ok(new_text)) = (
+        String::from_utf8(fs::read(old)?),
+        String::from_utf8(fs::read(new)?),
+    ) else {
+        return Ok(None);
+    };
+
+    let diff = TextDiff::from_lines(&old_text, &new_text)
+        .unified_diff()
+        .header("home", "group")
+        .to_string();
+    Ok(Some(diff))
+}
+
+#[cfg(test)]
+mod tests {
+    use pretty_assertions::assert_eq;
+
+    use super::*;
+    use crate::utils::test_utils::cd_to_testdir;
+
+    #[test]
+    fn test_parse_choice() {
+        assert_eq!(
+            ConflictChoice::parse("g\n"),
+            Some(ConflictChoice::KeepGroup)
+        );
+        assert_eq!(
+            ConflictChoice::parse("home"),
+            Some(ConflictChoice::KeepHome)
+        );
+        assert_eq!(ConflictChoice::parse(" r "), Some(ConflictChoice::Rename));
+        assert_eq!(ConflictChoice::parse("s"), Some(ConflictChoice::Skip));
+        assert_eq!(ConflictChoice::parse("x"), None);
+    }
+
+    #[test]
+    fn test_text_diff() {
+        let (_dropper, test_dir) = cd_to_testdir().unwrap();
+        let (home, group) = (test_dir.join("home"), test_dir.join("group"));
+        fs::write(&home, "a\nb\n").unwrap();
+        fs::write(&group, "a\nc\n").unwrap();
+
+        let diff = text_diff(&home, &group).unwrap().unwrap();
+        assert_eq!(diff, "--- home\n+++ group\n@@ -1,2 +1,2 @@\n a\n-b\n+c\n");
+
+        fs::write(&group, [0xff, 0xfe]).unwrap();
+        assert_eq!(text_diff(&home, &group).unwrap(), None);
+    }
+}
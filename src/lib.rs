@@ -1,7 +1,15 @@
 #![allow(irrefutable_let_patterns)] // crazy idiom
 
+pub mod alternate;
+pub mod catalog;
 pub mod commands;
 pub mod config;
+pub mod conflict;
+pub mod hooks;
+pub mod interactive;
+pub mod permissions;
+pub mod state;
+pub mod template;
 pub mod utils;
 
 pub type Result<T, E = eyre::Error> = std::result::Result<T, E>;
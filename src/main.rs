@@ -1,36 +1,162 @@
-use std::path::PathBuf;
+use std::path::{self, Path, PathBuf};
 
-use clap::Parser;
+use clap::{Parser, Subcommand};
 use dotin::{
     Result,
-    commands::{discard, import, link, unlink},
-    config::{init_config, read_config},
-    utils::{find_dotfiles_folder, get_home_dir, try_exists},
+    alternate::alternate_conditions,
+    catalog::Catalog,
+    commands::{
+        DiscardOptions, ImportOptions, LinkOptions, UnlinkOptions, diff, discard, group_diff,
+        import, link, merge_groups, move_paths, plan_discard_all, rename_group, repair, scan,
+        split_group, status, unlink,
+    },
+    config::{
+        init_config, merge_group_in_config, read_config, remove_group_from_config,
+        rename_group_in_config, save_override_base_folder,
+    },
+    hooks::{HookContext, Hooks},
+    permissions::Permissions,
+    state::State,
+    template::TemplateVariables,
+    utils::{
+        default_backup_suffix, find_dotfiles_folder, get_home_dir, list_groups,
+        remove_empty_group_folder, try_exists,
+    },
 };
 use eyre::{WrapErr, bail};
+use glob::Pattern;
 
 #[derive(Parser, Debug)]
 #[command(version, about)]
+struct Cli {
+    #[command(subcommand)]
+    command: Command,
+    /// Don't run the group hooks set in the config
+    #[arg(long, global = true)]
+    no_hooks: bool,
+    /// Use this base folder for the groups, instead of the one in the config (or home)
+    #[arg(long, global = true, value_name = "DIR")]
+    base: Option<PathBuf>,
+    /// Save `--base` in the config, as the base folder of the given groups
+    #[arg(long, global = true, requires = "base")]
+    save_base: bool,
+}
+
+#[derive(Subcommand, Debug)]
 enum Command {
-    /// Moves files into a specific dotfiles group folder and links them back
+    /// Moves files into a specific dotfiles group folder and links them back, quoted glob
+    /// patterns are expanded
     Import {
-        group_name: String,
-        #[arg(required = true)]
+        /// With `--auto`, the first file to import instead
+        #[arg(required_unless_present = "auto")]
+        group_name: Option<String>,
+        #[arg(required_unless_present = "auto")]
         files: Vec<PathBuf>,
+        /// Pick the group of each file from the catalog of well-known config paths (extended by
+        /// `[catalog]` in the config), the files are imported right away
+        #[arg(long)]
+        auto: bool,
         /// Skip linking files back to their original location after import
         #[arg(long)]
         no_link: bool,
+        /// Import as an alternate for machines matching `ATTRIBUTE=VALUE` (distro, class, host
+        /// or user), or `default`, can be repeated
+        #[arg(long, value_name = "ATTRIBUTE=VALUE")]
+        alternate: Vec<String>,
+        /// Leave files matching the glob at the base folder, relative to the imported path (or
+        /// to the base folder), can be repeated
+        #[arg(long, value_name = "GLOB")]
+        exclude: Vec<String>,
+        /// Ask how to resolve conflicts with files already in the group
+        #[arg(short, long)]
+        interactive: bool,
+        /// Import the content of symlinks' targets, instead of the symlinks themselves (which get
+        /// their relative targets rewritten to still resolve from the group)
+        #[arg(long)]
+        dereference: bool,
     },
     /// Move file back from a group to its target position (reverse of import)
     Discard {
         group_name: String,
-        #[arg(required = true)]
+        #[arg(required_unless_present = "all")]
         files: Vec<PathBuf>,
+        /// Discard every file of the group, then delete the group folder and its config entries
+        #[arg(long, conflicts_with = "files")]
+        all: bool,
+        /// Ask how to resolve conflicts with files already at the base folder
+        #[arg(short, long)]
+        interactive: bool,
+    },
+    /// Move files from a group to another, relinking them without leaving copies at the base
+    /// folder
+    Move {
+        from_group: String,
+        to_group: String,
+        #[arg(required = true)]
+        paths: Vec<PathBuf>,
+    },
+    /// Move files out of a group into a new group, relinking them
+    Split {
+        group_name: String,
+        new_group_name: String,
+        #[arg(required = true)]
+        paths: Vec<PathBuf>,
     },
+    /// Move every file of a group into another group, relinking them, then delete the first
+    Merge {
+        source_group: String,
+        destination_group: String,
+    },
+    /// Rename a group, relinking its files and renaming its config entries
+    RenameGroup { old_name: String, new_name: String },
     /// Link dotfiles groups into their target position
-    Link { groups: Vec<String> },
+    Link {
+        groups: Vec<String>,
+        /// Move conflicting files from the base folder into the group, then link them
+        #[arg(long)]
+        adopt: bool,
+        /// Let `--adopt` take directories that aren't empty, and replace rendered templates that
+        /// were edited since
+        #[arg(long)]
+        force: bool,
+        /// Rename conflicting files to `<name>.dotin-backup-<SUFFIX>` before linking, the suffix
+        /// defaults to the current timestamp
+        #[arg(
+            long,
+            value_name = "SUFFIX",
+            num_args = 0..=1,
+            require_equals = true,
+            conflicts_with = "adopt"
+        )]
+        backup: Option<Option<String>>,
+    },
+    /// Fix links that point into groups at an old dotfiles folder location
+    Repair { groups: Vec<String> },
     /// Removes links created by the `link` command
-    Unlink { groups: Vec<String> },
+    Unlink {
+        groups: Vec<String>,
+        /// Move backups made by `link --backup` back into place
+        #[arg(long)]
+        restore_backups: bool,
+    },
+    /// List configs at the base folder that aren't managed yet, as `dotin import` suggestions
+    Scan {
+        /// How deep to look inside of each candidate folder
+        #[arg(long, default_value_t = 4)]
+        depth: usize,
+    },
+    /// Show how group files differ from what is at the base folder, like copies or files in the
+    /// way of links (all groups if none is given)
+    Diff {
+        group: Option<String>,
+        /// Only compare these paths, at the base folder or in the group
+        paths: Vec<PathBuf>,
+        /// Print nothing, exit with status 1 if anything differs, and 2 on errors
+        #[arg(short, long)]
+        quiet: bool,
+    },
+    /// Show which files of each group are linked or rendered (all groups if none are given)
+    Status { groups: Vec<String> },
     /// Create config, or check its location
     Config {
         #[arg(short, long)]
@@ -38,14 +164,77 @@ enum Command {
     },
 }
 
+impl Command {
+    /// The groups the command was given.
+    fn groups(&self) -> &[String] {
+        match self {
+            Command::Import {
+                group_name, auto, ..
+            } => match auto {
+                true => &[],
+                false => group_name.as_slice(),
+            },
+            Command::Discard { group_name, .. } => std::slice::from_ref(group_name),
+            Command::Move { to_group, .. } => std::slice::from_ref(to_group),
+            Command::RenameGroup { new_name, .. } => std::slice::from_ref(new_name),
+            Command::Split { new_group_name, .. } => std::slice::from_ref(new_group_name),
+            Command::Merge {
+                destination_group, ..
+            } => std::slice::from_ref(destination_group),
+            Command::Link { groups, .. }
+            | Command::Repair { groups }
+            | Command::Unlink { groups, .. }
+            | Command::Status { groups } => groups,
+            Command::Diff { group, .. } => group.as_slice(),
+            Command::Scan { .. } | Command::Config { .. } => &[],
+        }
+    }
+}
+
 fn main() -> Result<()> {
     color_eyre::install().unwrap();
 
+    let cli = Cli::parse();
+    // like `diff` and `cmp`, 0 if nothing differs, 1 if anything does, and 2 on errors
+    let is_quiet_diff = matches!(cli.command, Command::Diff { quiet: true, .. });
+
+    match run(cli) {
+        Err(error) if is_quiet_diff => {
+            eprintln!("Error: {error:?}");
+            std::process::exit(2);
+        }
+        result => result,
+    }
+}
+
+fn run(cli: Cli) -> Result<()> {
     let home_dir = &get_home_dir()?;
     let dotfiles_folder = find_dotfiles_folder(home_dir)?;
-    let config = read_config(home_dir, &dotfiles_folder).wrap_err("Failed to read config")?;
+    let mut config = read_config(home_dir, &dotfiles_folder).wrap_err("Failed to read config")?;
+
+    let Cli {
+        command,
+        no_hooks,
+        base,
+        save_base,
+    } = cli;
 
-    let command = Command::parse();
+    // saved once the command succeeds, so a failed one doesn't affect later ones
+    let mut base_to_save = None;
+
+    if let Some(base) = base {
+        let base = path::absolute(base)?;
+
+        if save_base {
+            if command.groups().is_empty() {
+                bail!("--save-base needs the groups to save the base folder for");
+            }
+            base_to_save = Some((command.groups().to_vec(), base.clone()));
+        }
+
+        config.inner.base_folder = Some(base);
+    }
+    let hooks = Hooks::new(&config.inner, !no_hooks);
 
     // err early if trying to import or discard `"."`
     if let Command::Import { files, .. } | Command::Discard { files, .. } = &command
@@ -54,52 +243,257 @@ fn main() -> Result<()> {
         bail!("Cannot import or discard the current directory (\".\")");
     }
 
+    let mut exit_code = None;
+
     match command {
-        Command::Unlink { groups } => {
+        Command::Unlink {
+            groups,
+            restore_backups,
+        } => {
             if groups.is_empty() {
                 println!("list of groups to unlink is empty.");
                 return Ok(());
             }
 
+            let options = UnlinkOptions {
+                restore_backups,
+                template_variables: TemplateVariables::from_system(&config.inner),
+            };
+
+            let state_path = State::default_path(home_dir);
+            let mut state = State::read(&state_path)?;
+
+            // a failing pre-unlink hook stops before any group is unlinked
+            for group in &groups {
+                let base_folder = config.inner.base_folder_for_group(home_dir, group);
+                hooks.run(
+                    "pre-unlink",
+                    &HookContext {
+                        group,
+                        base_folder: &base_folder,
+                        group_folder: &dotfiles_folder.join(group),
+                        changed_paths: &recorded_symlinks(&mut state, group),
+                    },
+                )?;
+            }
+
+            let mut unlinked_per_group = Vec::with_capacity(groups.len());
+            for group in &groups {
+                let base_folder = config.inner.base_folder_for_group(home_dir, group);
+                let linked = recorded_symlinks(&mut state, group);
+
+                let result = unlink(
+                    &base_folder,
+                    &dotfiles_folder.join(group),
+                    &options,
+                    state.group_mut(group),
+                );
+                let unlinked = difference(&linked, &recorded_symlinks(&mut state, group));
+                state.remove_empty_groups();
+                state.write(&state_path)?;
+                result.wrap_err_with(|| format!("Failed to unlink group \"{group}\""))?;
+
+                unlinked_per_group.push(unlinked);
+            }
+
+            for (group, unlinked) in groups.iter().zip(&unlinked_per_group) {
+                hooks.run(
+                    "post-unlink",
+                    &HookContext {
+                        group,
+                        base_folder: &config.inner.base_folder_for_group(home_dir, group),
+                        group_folder: &dotfiles_folder.join(group),
+                        changed_paths: unlinked,
+                    },
+                )?;
+            }
+        }
+        Command::Link {
+            groups,
+            adopt,
+            force,
+            backup,
+        } => {
+            if groups.is_empty() {
+                println!("No group list provided.");
+            }
+
+            let options = LinkOptions {
+                adopt,
+                force,
+                backup: backup.map(|suffix| suffix.unwrap_or_else(default_backup_suffix)),
+                template_variables: TemplateVariables::from_system(&config.inner),
+                permissions: Permissions::default(),
+            };
+
+            let state_path = State::default_path(home_dir);
+            let mut state = State::read(&state_path)?;
+
+            // a failing pre-link hook stops before any group is linked
             for group in &groups {
                 let base_folder = config.inner.base_folder_for_group(home_dir, group);
+                run_pre_link_hook(hooks, group, &base_folder, &dotfiles_folder.join(group))?;
+            }
+
+            for (index, group) in groups.iter().enumerate() {
+                let base_folder = config.inner.base_folder_for_group(home_dir, group);
 
-                unlink(&base_folder, &dotfiles_folder.join(group))
-                    .wrap_err_with(|| format!("Failed to unlink group \"{group}\""))?;
+                let result = config
+                    .inner
+                    .permissions_for_group(group)
+                    .and_then(|permissions| {
+                        let options = LinkOptions {
+                            permissions,
+                            ..options.clone()
+                        };
+                        link_group(
+                            hooks,
+                            &mut state,
+                            &state_path,
+                            group,
+                            &base_folder,
+                            &dotfiles_folder.join(group),
+                            &options,
+                        )
+                    });
+
+                if index > 0 {
+                    result.wrap_err_with(|| {
+                        format!("Groups linked before the failure: {:?}", &groups[..index])
+                    })?;
+                } else {
+                    result?;
+                }
             }
         }
-        Command::Link { groups } => {
+        Command::Repair { groups } => {
             if groups.is_empty() {
                 println!("No group list provided.");
             }
 
             for group in &groups {
-                let dotfiles_group_folder = &dotfiles_folder.join(group);
                 let base_folder = config.inner.base_folder_for_group(home_dir, group);
 
-                link(&base_folder, dotfiles_group_folder)
-                    .wrap_err_with(|| format!("Failed to link group \"{group}\""))?;
+                repair(&base_folder, &dotfiles_folder.join(group))
+                    .wrap_err_with(|| format!("Failed to repair group \"{group}\""))?;
             }
         }
         Command::Import {
             group_name,
             files,
+            auto,
             no_link,
+            alternate,
+            exclude,
+            interactive,
+            dereference,
         } => {
-            assert!(!files.is_empty(), "ensured by CLI definitions");
-            let base_folder = config.inner.base_folder_for_group(home_dir, &group_name);
-            let group_folder = dotfiles_folder.join(&group_name);
+            let options = ImportOptions {
+                alternate: (!alternate.is_empty())
+                    .then(|| alternate_conditions(&alternate))
+                    .transpose()?,
+                exclude: exclude
+                    .iter()
+                    .map(|pattern| {
+                        Pattern::new(pattern)
+                            .wrap_err_with(|| format!("invalid exclude pattern {pattern:?}"))
+                    })
+                    .collect::<Result<_>>()?,
+                interactive,
+                dereference,
+            };
+
+            let groups = if auto {
+                // the first positional argument is a file too
+                let files = group_name.map(PathBuf::from).into_iter().chain(files);
+                let files = files.collect::<Vec<_>>();
+                if files.is_empty() {
+                    bail!("No files given to import");
+                }
+                if files.iter().any(|file| file == ".") {
+                    bail!("Cannot import or discard the current directory (\".\")");
+                }
+
+                let base_folder = config.inner.base_folder.as_deref().unwrap_or(home_dir);
+                let groups = Catalog::new(&config.inner.catalog)
+                    .group_paths(base_folder, &files)
+                    .wrap_err("Failed to group files by the catalog")?;
+
+                println!("Importing by catalog group:");
+                for (group, files) in &groups {
+                    println!("  {group}: {files:?}");
+                }
+
+                groups.into_iter().collect::<Vec<_>>()
+            } else {
+                let group_name = group_name.expect("ensured by CLI definitions");
+                assert!(!files.is_empty(), "ensured by CLI definitions");
+                vec![(group_name, files)]
+            };
 
-            import(&base_folder, &group_folder, &files)
-                .wrap_err_with(|| format!("Failed to import files for group \"{group_name}\""))?;
+            // a failing pre-import or pre-link hook stops before any file is moved
+            for (group, files) in &groups {
+                let base_folder = config.inner.base_folder_for_group(home_dir, group);
+                let group_folder = dotfiles_folder.join(group);
 
-            if !no_link {
-                link(&base_folder, &group_folder)
-                    .wrap_err_with(|| format!("Failed to link group \"{group_name}\""))?;
+                hooks.run(
+                    "pre-import",
+                    &HookContext {
+                        group,
+                        base_folder: &base_folder,
+                        group_folder: &group_folder,
+                        changed_paths: files,
+                    },
+                )?;
+                if !no_link {
+                    run_pre_link_hook(hooks, group, &base_folder, &group_folder)?;
+                }
+            }
+
+            let state_path = State::default_path(home_dir);
+            let mut state = State::read(&state_path)?;
+
+            for (group, files) in &groups {
+                let base_folder = config.inner.base_folder_for_group(home_dir, group);
+                let group_folder = dotfiles_folder.join(group);
+
+                import(&base_folder, &group_folder, files, &options)
+                    .wrap_err_with(|| format!("Failed to import files for group \"{group}\""))?;
+                hooks.run(
+                    "post-import",
+                    &HookContext {
+                        group,
+                        base_folder: &base_folder,
+                        group_folder: &group_folder,
+                        changed_paths: files,
+                    },
+                )?;
+
+                if !no_link {
+                    let link_options = LinkOptions {
+                        template_variables: TemplateVariables::from_system(&config.inner),
+                        permissions: config.inner.permissions_for_group(group)?,
+                        ..LinkOptions::default()
+                    };
+
+                    link_group(
+                        hooks,
+                        &mut state,
+                        &state_path,
+                        group,
+                        &base_folder,
+                        &group_folder,
+                        &link_options,
+                    )?;
+                }
             }
         }
-        Command::Discard { group_name, files } => {
-            assert!(!files.is_empty(), "ensured by CLI definitions");
+        Command::Discard {
+            group_name,
+            files,
+            all,
+            interactive,
+        } => {
             if !try_exists(dotfiles_folder.join(&group_name))? {
                 println!(
                     "Group \"{group_name}\" does not exist at {:?}.",
@@ -108,9 +502,244 @@ fn main() -> Result<()> {
                 return Ok(());
             }
             let base_folder = config.inner.base_folder_for_group(home_dir, &group_name);
+            let group_folder = dotfiles_folder.join(&group_name);
+
+            let files = if all {
+                plan_discard_all(&base_folder, &group_folder)?
+            } else {
+                assert!(!files.is_empty(), "ensured by CLI definitions");
+                files
+            };
+
+            let context = HookContext {
+                group: &group_name,
+                base_folder: &base_folder,
+                group_folder: &group_folder,
+                changed_paths: &files,
+            };
+
+            hooks.run("pre-discard", &context)?;
+            discard(
+                &base_folder,
+                &group_folder,
+                &files,
+                &DiscardOptions { interactive },
+            )
+            .wrap_err_with(|| format!("Failed to discard files for group \"{group_name}\""))?;
+            hooks.run("post-discard", &context)?;
+
+            if all && remove_empty_group_folder(&group_folder)? {
+                let state_path = State::default_path(home_dir);
+                let mut state = State::read(&state_path)?;
+                state.groups.shift_remove(&group_name);
+                state.write(&state_path)?;
+
+                remove_group_from_config(&config, &group_name)?;
+                // the group is retired, don't bring its config entries back
+                base_to_save = None;
+            }
+        }
+        Command::Move {
+            from_group,
+            to_group,
+            paths,
+        } => {
+            if from_group == to_group {
+                bail!("Can't move files from group \"{from_group}\" into itself");
+            }
+            let from_folder = dotfiles_folder.join(&from_group);
+            if !try_exists(&from_folder)? {
+                bail!("Group \"{from_group}\" does not exist at {from_folder:?}");
+            }
+
+            let base_folder = config.inner.base_folder_for_group(home_dir, &from_group);
+            if base_folder != config.inner.base_folder_for_group(home_dir, &to_group) {
+                bail!(
+                    "Can't move files from group \"{from_group}\" to \"{to_group}\", their base \
+                     folders differ"
+                );
+            }
+
+            let relinked = move_paths(
+                &base_folder,
+                &from_folder,
+                &dotfiles_folder.join(&to_group),
+                &paths,
+            )
+            .wrap_err_with(|| {
+                format!("Failed to move files from group \"{from_group}\" to \"{to_group}\"")
+            })?;
+
+            record_moved_links(home_dir, &from_group, &to_group, relinked)?;
+        }
+        Command::Split {
+            group_name,
+            new_group_name,
+            paths,
+        } => {
+            let group_folder = dotfiles_folder.join(&group_name);
+            if !try_exists(&group_folder)? {
+                bail!("Group \"{group_name}\" does not exist at {group_folder:?}");
+            }
+            if config.inner.has_entries_for_group(&new_group_name) {
+                bail!(
+                    "Can't split into group \"{new_group_name}\", the config already has entries \
+                     for it"
+                );
+            }
+
+            let base_folder = config.inner.base_folder_for_group(home_dir, &group_name);
+            let relinked = split_group(
+                &base_folder,
+                &group_folder,
+                &dotfiles_folder.join(&new_group_name),
+                &paths,
+            )
+            .wrap_err_with(|| format!("Failed to split group \"{group_name}\""))?;
+
+            // the new group keeps the base folder of the files
+            if config.inner.override_base_folder.contains_key(&group_name) {
+                save_override_base_folder(home_dir, &config, &new_group_name, &base_folder)?;
+            }
+
+            record_moved_links(home_dir, &group_name, &new_group_name, relinked)?;
+        }
+        Command::Merge {
+            source_group,
+            destination_group,
+        } => {
+            if source_group == destination_group {
+                bail!("Can't merge group \"{source_group}\" into itself");
+            }
+
+            let base_folder = config.inner.base_folder_for_group(home_dir, &source_group);
+            if base_folder
+                != config
+                    .inner
+                    .base_folder_for_group(home_dir, &destination_group)
+            {
+                bail!(
+                    "Can't merge group \"{source_group}\" into \"{destination_group}\", their base \
+                     folders differ"
+                );
+            }
+
+            merge_groups(
+                &base_folder,
+                &dotfiles_folder.join(&source_group),
+                &dotfiles_folder.join(&destination_group),
+            )
+            .wrap_err_with(|| {
+                format!("Failed to merge group \"{source_group}\" into \"{destination_group}\"")
+            })?;
+
+            let state_path = State::default_path(home_dir);
+            let mut state = State::read(&state_path)?;
+            if let Some(source_state) = state.groups.shift_remove(&source_group) {
+                let destination_state = state.group_mut(&destination_group);
+                destination_state.symlinks.extend(source_state.symlinks);
+                destination_state
+                    .directories
+                    .extend(source_state.directories);
+                destination_state.rendered.extend(source_state.rendered);
+                state.write(&state_path)?;
+            }
+
+            merge_group_in_config(&config, &source_group, &destination_group)?;
 
-            discard(&base_folder, &dotfiles_folder.join(&group_name), &files)
-                .wrap_err_with(|| format!("Failed to discard files for group \"{group_name}\""))?;
+            if config.inner.hooks.contains_key(&source_group)
+                || config.inner.permissions.contains_key(&source_group)
+            {
+                println!(
+                    "WARNING: the config still has hooks or permissions of group \"{source_group}\", \
+                     move them to \"{destination_group}\" if they're still needed"
+                );
+            }
+        }
+        Command::RenameGroup { old_name, new_name } => {
+            if config.inner.has_entries_for_group(&new_name) {
+                bail!(
+                    "Can't rename group to \"{new_name}\", the config already has entries for it"
+                );
+            }
+
+            let base_folder = config.inner.base_folder_for_group(home_dir, &old_name);
+            rename_group(
+                &base_folder,
+                &dotfiles_folder.join(&old_name),
+                &dotfiles_folder.join(&new_name),
+            )
+            .wrap_err_with(|| format!("Failed to rename group \"{old_name}\""))?;
+
+            let state_path = State::default_path(home_dir);
+            let mut state = State::read(&state_path)?;
+            if let Some(group_state) = state.groups.shift_remove(&old_name) {
+                state.groups.insert(new_name.clone(), group_state);
+                state.write(&state_path)?;
+            }
+
+            rename_group_in_config(&config, &old_name, &new_name)?;
+        }
+        Command::Status { groups } => {
+            let groups = if groups.is_empty() {
+                list_groups(&dotfiles_folder)?
+            } else {
+                groups
+            };
+            let template_variables = TemplateVariables::from_system(&config.inner);
+
+            for group in &groups {
+                let base_folder = config.inner.base_folder_for_group(home_dir, group);
+
+                println!("Group \"{group}\":");
+                status(
+                    &base_folder,
+                    &dotfiles_folder.join(group),
+                    &template_variables,
+                    &config.inner.permissions_for_group(group)?,
+                )
+                .wrap_err_with(|| format!("Failed to read status of group \"{group}\""))?;
+            }
+        }
+        Command::Diff {
+            group,
+            paths,
+            quiet,
+        } => {
+            let groups = match group {
+                Some(group) => vec![group],
+                None => list_groups(&dotfiles_folder)?,
+            };
+            let template_variables = TemplateVariables::from_system(&config.inner);
+
+            let mut differs = false;
+            for group in &groups {
+                let base_folder = config.inner.base_folder_for_group(home_dir, group);
+                let group_folder = dotfiles_folder.join(group);
+                if !try_exists(&group_folder)? {
+                    bail!("Group \"{group}\" does not exist at {group_folder:?}");
+                }
+
+                let result = if quiet {
+                    group_diff(&base_folder, &group_folder, &paths, &template_variables)
+                        .map(|entries| !entries.is_empty())
+                } else {
+                    println!("Group \"{group}\":");
+                    diff(&base_folder, &group_folder, &paths, &template_variables)
+                };
+                differs |= result.wrap_err_with(|| format!("Failed to diff group \"{group}\""))?;
+            }
+
+            if quiet {
+                exit_code = Some(i32::from(differs));
+            }
+        }
+        Command::Scan { depth } => {
+            let base_folder = config.inner.base_folder.as_deref().unwrap_or(home_dir);
+            let catalog = Catalog::new(&config.inner.catalog);
+
+            scan(base_folder, home_dir, &dotfiles_folder, &catalog, depth)
+                .wrap_err("Failed to scan for unmanaged configs")?;
         }
         Command::Config { init } => {
             if init {
@@ -126,6 +755,90 @@ fn main() -> Result<()> {
         }
     }
 
+    if let Some((groups, base)) = base_to_save {
+        for group in &groups {
+            save_override_base_folder(home_dir, &config, group, &base)?;
+        }
+    }
+
+    if let Some(exit_code) = exit_code {
+        std::process::exit(exit_code);
+    }
+
     println!("Done.");
     Ok(())
 }
+
+fn run_pre_link_hook(
+    hooks: Hooks,
+    group: &str,
+    base_folder: &Path,
+    group_folder: &Path,
+) -> Result<()> {
+    hooks.run(
+        "pre-link",
+        &HookContext {
+            group,
+            base_folder,
+            group_folder,
+            changed_paths: &[],
+        },
+    )
+}
+
+/// Links a group, then runs its `post-link` hook, recording it in the state.
+fn link_group(
+    hooks: Hooks,
+    state: &mut State,
+    state_path: &Path,
+    group: &str,
+    base_folder: &Path,
+    group_folder: &Path,
+    options: &LinkOptions,
+) -> Result<()> {
+    let linked_before = recorded_symlinks(state, group);
+
+    let result = link(base_folder, group_folder, options, state.group_mut(group));
+    let linked = difference(&recorded_symlinks(state, group), &linked_before);
+    state.remove_empty_groups();
+    state.write(state_path)?;
+    result.wrap_err_with(|| format!("Failed to link group \"{group}\""))?;
+
+    hooks.run(
+        "post-link",
+        &HookContext {
+            group,
+            base_folder,
+            group_folder,
+            changed_paths: &linked,
+        },
+    )
+}
+
+/// Moves the links from a group to another in the state.
+fn record_moved_links(
+    home_dir: &Path,
+    from_group: &str,
+    to_group: &str,
+    links: Vec<PathBuf>,
+) -> Result<()> {
+    let state_path = State::default_path(home_dir);
+    let mut state = State::read(&state_path)?;
+
+    for link in links {
+        state.group_mut(from_group).symlinks.shift_remove(&link);
+        state.group_mut(to_group).symlinks.insert(link);
+    }
+
+    state.remove_empty_groups();
+    state.write(&state_path)
+}
+
+fn recorded_symlinks(state: &mut State, group: &str) -> Vec<PathBuf> {
+    state.group_mut(group).symlinks.iter().cloned().collect()
+}
+
+/// Paths in `a` that aren't in `b`.
+fn difference(a: &[PathBuf], b: &[PathBuf]) -> Vec<PathBuf> {
+    a.iter().filter(|path| !b.contains(path)).cloned().collect()
+}
@@ -0,0 +1,210 @@
+use std::{
+    ffi::OsStr,
+    os::unix::fs::PermissionsExt,
+    path::{Component, Path, PathBuf},
+};
+
+use eyre::{WrapErr, bail, eyre};
+use fs_err as fs;
+use fs_tree::FsTree;
+use glob::{MatchOptions, Pattern};
+use indexmap::IndexMap;
+
+use crate::{Result, alternate::split_alternate, template::template_output_path};
+
+const MATCH_OPTIONS: MatchOptions = MatchOptions {
+    case_sensitive: true,
+    require_literal_separator: true,
+    require_literal_leading_dot: false,
+};
+
+/// Modes declared for paths of a group, like `".ssh/config" = "0600"`.
+///
+/// Patterns match the path at the base folder (so templates and alternates match by their plain
+/// path), and the last matching pattern wins.
+#[derive(Debug, Default, Clone)]
+pub struct Permissions {
+    rules: Vec<(Pattern, u32)>,
+}
+
+/// A group file whose mode differs from the declared one.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ModeMismatch {
+    /// Path relative to the group folder.
+    pub path: PathBuf,
+    pub actual: u32,
+    pub declared: u32,
+}
+
+impl Permissions {
+    /// Parses the `[permissions.<group>]` config section.
+    pub fn parse(declared: &IndexMap<String, String>) -> Result<Self> {
+        let rules = declared
+            .iter()
+            .map(|(pattern, mode)| {
+                let pattern = Pattern::new(pattern)
+                    .wrap_err_with(|| format!("invalid permissions pattern {pattern:?}"))?;
+                Ok((pattern, parse_mode(mode)?))
+            })
+            .collect::<Result<_>>()?;
+
+        Ok(Self { rules })
+    }
+
+    /// The mode declared for `relative_path`, a path relative to the group folder.
+    pub fn mode_for(&self, relative_path: &Path) -> Option<u32> {
+        let declared_path = declared_path(relative_path);
+
+        self.rules
+            .iter()
+            .rev()
+            .find(|(pattern, _)| pattern.matches_path_with(&declared_path, MATCH_OPTIONS))
+            .map(|(_, mode)| *mode)
+    }
+
+    /// Group files (not symlinks) whose mode differs from the declared one.
+    pub fn mismatches(&self, group_dir: &Path) -> Result<Vec<ModeMismatch>> {
+        if self.rules.is_empty() {
+            return Ok(vec![]);
+        }
+
+        let group_tree =
+            FsTree::symlink_read_at(group_dir).wrap_err("reading dotfiles folder tree")?;
+        let mut mismatches = vec![];
+
+        for (node, relative_path) in group_tree.iter().min_depth(1) {
+            if node.is_symlink() {
+                continue;
+            }
+            let Some(declared) = self.mode_for(&relative_path) else {
+                continue;
+            };
+
+            let actual = fs::symlink_metadata(group_dir.join(&relative_path))?
+                .permissions()
+                .mode()
+                & 0o7777;
+
+            if actual != declared {
+                mismatches.push(ModeMismatch {
+                    path: relative_path,
+                    actual,
+                    declared,
+                });
+            }
+        }
+
+        Ok(mismatches)
+    }
+
+    /// Sets the declared modes on the group files.
+    pub fn apply(&self, group_dir: &Path) -> Result<()> {
+        for mismatch in self.mismatches(group_dir)? {
+            let path = group_dir.join(&mismatch.path);
+            fs::set_permissions(&path, PermissionsExt::from_mode(mismatch.declared))?;
+            println!(
+                "Changed mode of {path:?} from {:04o} to {:04o}",
+                mismatch.actual, mismatch.declared,
+            );
+        }
+        Ok(())
+    }
+}
+
+/// Parses an octal mode like `"0600"`.
+pub fn parse_mode(mode: &str) -> Result<u32> {
+    let parsed = u32::from_str_radix(mode, 8)
+        .map_err(|_| eyre!("invalid mode {mode:?}, expected octal like \"0600\""))?;
+
+    if parsed > 0o7777 {
+        bail!("invalid mode {mode:?}, too large");
+    }
+    Ok(parsed)
+}
+
+/// The path at the base folder for a group path, without alternate conditions and the template
+/// extension.
+pub(crate) fn declared_path(relative_path: &Path) -> PathBuf {
+    let plain_path = relative_path
+        .components()
+        .map(|component| match component {
+            Component::Normal(name) => match name.to_str().and_then(split_alternate) {
+                Some((plain_name, _)) => OsStr::new(plain_name),
+                None => name,
+            },
+            other => other.as_os_str(),
+        })
+        .collect::<PathBuf>();
+
+    template_output_path(&plain_path).unwrap_or(plain_path)
+}
+
+#[cfg(test)]
+mod tests {
+    use fs_tree::tree;
+    use pretty_assertions::assert_eq;
+
+    use super::*;
+    use crate::utils::test_utils::cd_to_testdir;
+
+    fn permissions(declared: &[(&str, &str)]) -> Permissions {
+        let declared = declared
+            .iter()
+            .map(|(pattern, mode)| (pattern.to_string(), mode.to_string()))
+            .collect();
+        Permissions::parse(&declared).unwrap()
+    }
+
+    #[test]
+    fn test_mode_for() {
+        let permissions = permissions(&[(".gnupg/**", "0700"), (".ssh/config", "0600")]);
+
+        let mode_for = |path: &str| permissions.mode_for(Path::new(path));
+        assert_eq!(mode_for(".ssh/config"), Some(0o600));
+        assert_eq!(mode_for(".ssh/config##host.desk"), Some(0o600));
+        assert_eq!(mode_for(".ssh/config.dotin.tmpl"), Some(0o600));
+        assert_eq!(mode_for(".ssh/known_hosts"), None);
+        assert_eq!(mode_for(".gnupg/private-keys-v1.d/key"), Some(0o700));
+
+        assert!(parse_mode("0600").is_ok());
+        assert!(parse_mode("rw").is_err());
+        assert!(parse_mode("17777").is_err());
+    }
+
+    #[test]
+    fn test_apply_permissions() {
+        let (_dropper, test_dir) = cd_to_testdir().unwrap();
+        let group_dir = test_dir.join("ssh");
+
+        tree! {
+            ssh: [
+                ".ssh": [
+                    config
+                    known_hosts
+                ]
+            ]
+        }
+        .write_structure_at(".")
+        .unwrap();
+        let config_path = group_dir.join(".ssh/config");
+        fs::set_permissions(&config_path, PermissionsExt::from_mode(0o644)).unwrap();
+
+        let permissions = permissions(&[(".ssh/config", "0600")]);
+
+        let mismatches = permissions.mismatches(&group_dir).unwrap();
+        let expected = ModeMismatch {
+            path: ".ssh/config".into(),
+            actual: 0o644,
+            declared: 0o600,
+        };
+        assert_eq!(mismatches, [expected]);
+
+        permissions.apply(&group_dir).unwrap();
+        let mode = fs::symlink_metadata(&config_path)
+            .unwrap()
+            .permissions()
+            .mode();
+        assert_eq!(mode & 0o7777, 0o600);
+        assert!(permissions.mismatches(&group_dir).unwrap().is_empty());
+    }
+}
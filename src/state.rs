@@ -0,0 +1,134 @@
+use std::{
+    env,
+    path::{Path, PathBuf},
+};
+
+use eyre::WrapErr;
+use fs_err as fs;
+use indexmap::{IndexMap, IndexSet};
+use serde::{Deserialize, Serialize};
+
+use crate::{Result, utils::try_exists};
+
+/// Everything `dotin` created on this machine, per group, so it can be removed exactly.
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct State {
+    #[serde(default)]
+    pub groups: IndexMap<String, GroupState>,
+}
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct GroupState {
+    /// Symlinks created at the base folder.
+    #[serde(default)]
+    pub symlinks: IndexSet<PathBuf>,
+    /// Directories created at the base folder, to hold the symlinks.
+    #[serde(default)]
+    pub directories: IndexSet<PathBuf>,
+    /// Template outputs rendered at the base folder, with the hash of their content, to tell if
+    /// they were edited since.
+    #[serde(default)]
+    pub rendered: IndexMap<PathBuf, String>,
+}
+
+impl GroupState {
+    pub fn is_empty(&self) -> bool {
+        self.symlinks.is_empty() && self.directories.is_empty() && self.rendered.is_empty()
+    }
+
+    /// Records `content` as the last render at `output_path`.
+    pub fn record_render(&mut self, output_path: &Path, content: &[u8]) {
+        self.rendered
+            .insert(output_path.to_owned(), render_hash(content));
+    }
+
+    /// Checks if `content` is what was last rendered at `output_path`.
+    pub fn is_last_render(&self, output_path: &Path, content: &[u8]) -> bool {
+        self.rendered.get(output_path) == Some(&render_hash(content))
+    }
+}
+
+fn render_hash(content: &[u8]) -> String {
+    format!("{:016x}", rapidhash::v3::rapidhash_v3(content))
+}
+
+impl State {
+    /// `$XDG_STATE_HOME/dotin/state.toml`, defaults to `~/.local/state/dotin/state.toml`.
+    pub fn default_path(home: &Path) -> PathBuf {
+        let state_home = env::var_os("XDG_STATE_HOME")
+            .map(PathBuf::from)
+            .filter(|path| path.is_absolute())
+            .unwrap_or_else(|| home.join(".local/state"));
+
+        state_home.join("dotin/state.toml")
+    }
+
+    /// Reads the state at `path`, or an empty one if there's no file.
+    pub fn read(path: &Path) -> Result<Self> {
+        if !try_exists(path)? {
+            return Ok(Self::default());
+        }
+
+        let contents = fs::read_to_string(path)?;
+        toml::from_str(&contents).wrap_err_with(|| format!("Failed to parse state file {path:?}"))
+    }
+
+    pub fn write(&self, path: &Path) -> Result<()> {
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+
+        let contents = toml::to_string(self).wrap_err("Failed to serialize state")?;
+        fs::write(path, contents)?;
+        Ok(())
+    }
+
+    pub fn group_mut(&mut self, group: &str) -> &mut GroupState {
+        self.groups.entry(group.to_owned()).or_default()
+    }
+
+    /// Forgets groups with nothing recorded.
+    pub fn remove_empty_groups(&mut self) {
+        self.groups.retain(|_, group_state| !group_state.is_empty());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use pretty_assertions::assert_eq;
+
+    use super::*;
+    use crate::utils::test_utils::cd_to_testdir;
+
+    #[test]
+    fn test_state_roundtrip() {
+        let (_dropper, test_dir) = cd_to_testdir().unwrap();
+        let path = test_dir.join("state/dotin/state.toml");
+
+        let mut state = State::read(&path).unwrap();
+        assert!(state.groups.is_empty());
+
+        let group_state = state.group_mut("i3");
+        group_state
+            .symlinks
+            .insert("/home/me/.config/i3/config".into());
+        group_state.directories.insert("/home/me/.config/i3".into());
+        state.group_mut("empty");
+        state.remove_empty_groups();
+        state.write(&path).unwrap();
+
+        let state = State::read(&path).unwrap();
+        assert_eq!(state.groups.keys().collect::<Vec<_>>(), ["i3"]);
+        let group_state = &state.groups["i3"];
+        assert!(
+            group_state
+                .symlinks
+                .contains(Path::new("/home/me/.config/i3/config"))
+        );
+        assert!(
+            group_state
+                .directories
+                .contains(Path::new("/home/me/.config/i3"))
+        );
+    }
+}
@@ -0,0 +1,168 @@
+use std::{
+    env,
+    path::{Path, PathBuf},
+    process,
+};
+
+use eyre::{bail, eyre};
+use fs_err as fs;
+use indexmap::IndexMap;
+
+use crate::{Result, config::Config};
+
+/// Group files ending with this extension are rendered at link time instead of linked.
+pub const TEMPLATE_EXTENSION: &str = ".dotin.tmpl";
+
+/// Variables available inside of templates as `{{ name }}`.
+#[derive(Debug, Default, Clone)]
+pub struct TemplateVariables {
+    pub inner: IndexMap<String, String>,
+}
+
+impl TemplateVariables {
+    /// Collects `hostname`, `username`, `os_*` fields from `os-release`, and the `[variables]`
+    /// from the config, which take precedence.
+    pub fn from_system(config: &Config) -> Self {
+        let mut inner = IndexMap::new();
+
+        if let Some(hostname) = read_hostname() {
+            inner.insert("hostname".to_owned(), hostname);
+        }
+
+        if let Some(username) = env::var_os("USER").or_else(|| env::var_os("LOGNAME")) {
+            inner.insert(
+                "username".to_owned(),
+                username.to_string_lossy().into_owned(),
+            );
+        }
+
+        for path in ["/etc/os-release", "/usr/lib/os-release"] {
+            if let Ok(contents) = fs::read_to_string(path) {
+                inner.extend(parse_os_release(&contents));
+                break;
+            }
+        }
+
+        inner.extend(config.variables.clone());
+
+        Self { inner }
+    }
+}
+
+fn read_hostname() -> Option<String> {
+    let output = process::Command::new("uname").arg("-n").output().ok()?;
+    let hostname = String::from_utf8(output.stdout).ok()?.trim().to_owned();
+    (output.status.success() && !hostname.is_empty()).then_some(hostname)
+}
+
+/// Parses `KEY="value"` lines into `os_key` variables.
+fn parse_os_release(contents: &str) -> impl Iterator<Item = (String, String)> {
+    contents
+        .lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty() && !line.starts_with('#'))
+        .filter_map(|line| line.split_once('='))
+        .map(|(key, value)| {
+            let value = value.trim_matches(|c| c == '"' || c == '\'');
+            (format!("os_{}", key.to_lowercase()), value.to_owned())
+        })
+}
+
+/// Returns the output path of a template, `None` if `path` isn't a template.
+pub fn template_output_path(path: &Path) -> Option<PathBuf> {
+    let file_name = path.file_name()?.to_str()?;
+    let output_name = file_name.strip_suffix(TEMPLATE_EXTENSION)?;
+
+    if output_name.is_empty() {
+        return None;
+    }
+
+    Some(path.with_file_name(output_name))
+}
+
+/// Replaces every `{{ name }}` in `template` by the value of the variable.
+pub fn render(template: &str, variables: &TemplateVariables) -> Result<String> {
+    let mut output = String::with_capacity(template.len());
+    let mut rest = template;
+
+    while let Some(start) = rest.find("{{") {
+        output.push_str(&rest[..start]);
+
+        let Some(end) = rest[start..].find("}}") else {
+            bail!("unterminated \"{{{{\" in template");
+        };
+
+        let name = rest[start + 2..start + end].trim();
+        let value = variables
+            .inner
+            .get(name)
+            .ok_or_else(|| eyre!("unknown template variable {name:?}"))?;
+
+        output.push_str(value);
+        rest = &rest[start + end + 2..];
+    }
+
+    output.push_str(rest);
+    Ok(output)
+}
+
+/// Reads and renders the template at `path`.
+pub fn render_file(path: &Path, variables: &TemplateVariables) -> Result<String> {
+    let template = fs::read_to_string(path)?;
+    render(&template, variables).map_err(|err| err.wrap_err(format!("rendering {path:?}")))
+}
+
+#[cfg(test)]
+mod tests {
+    use pretty_assertions::assert_eq;
+
+    use super::*;
+
+    fn variables() -> TemplateVariables {
+        TemplateVariables {
+            inner: [("hostname", "desk"), ("font_size", "12")]
+                .map(|(name, value)| (name.to_owned(), value.to_owned()))
+                .into_iter()
+                .collect(),
+        }
+    }
+
+    #[test]
+    fn test_render() {
+        let rendered = render("font: {{font_size}}px on {{ hostname }}\n", &variables()).unwrap();
+        assert_eq!(rendered, "font: 12px on desk\n");
+    }
+
+    #[test]
+    fn test_render_fails_on_unknown_or_unterminated() {
+        let error = render("{{ email }}", &variables()).unwrap_err().to_string();
+        assert!(error.contains("unknown template variable"), "msg = {error}");
+
+        let error = render("{{ hostname", &variables()).unwrap_err().to_string();
+        assert!(error.contains("unterminated"), "msg = {error}");
+    }
+
+    #[test]
+    fn test_parse_os_release() {
+        let variables: Vec<_> =
+            parse_os_release("# comment\nID=arch\nPRETTY_NAME=\"Arch Linux\"\n").collect();
+
+        assert_eq!(
+            variables,
+            [
+                ("os_id".to_owned(), "arch".to_owned()),
+                ("os_pretty_name".to_owned(), "Arch Linux".to_owned()),
+            ],
+        );
+    }
+
+    #[test]
+    fn test_template_output_path() {
+        assert_eq!(
+            template_output_path(Path::new(".config/foot/foot.ini.dotin.tmpl")),
+            Some(PathBuf::from(".config/foot/foot.ini")),
+        );
+        assert_eq!(template_output_path(Path::new(".config/foot.ini")), None);
+        assert_eq!(template_output_path(Path::new(".dotin.tmpl")), None);
+    }
+}
@@ -1,14 +1,19 @@
 use std::{
     env,
     ffi::{OsStr, OsString},
-    fmt, io,
+    fmt,
+    io::{self, BufRead, BufReader, Read},
     iter::repeat_n,
-    os::unix::fs::{MetadataExt, symlink},
-    path::{Path, PathBuf},
+    os::unix::fs::{MetadataExt, lchown, symlink},
+    path::{Component, Path, PathBuf},
+    process,
+    time::{SystemTime, UNIX_EPOCH},
 };
 
-use eyre::{OptionExt, WrapErr, eyre};
+use eyre::{OptionExt, WrapErr, bail, eyre};
+use filetime::FileTime;
 use fs_err as fs;
+use fs_tree::FsTree;
 use indexmap::IndexMap;
 
 use crate::Result;
@@ -44,12 +49,65 @@ pub fn read_file_type(path: impl AsRef<Path>) -> Result<FileType> {
     }
 }
 
-pub fn find_dotfiles_folder(home_dir: &Path) -> Result<PathBuf> {
-    const CANDIDATES: &[&str] = &["dotfiles", ".dotfiles", "dots", ".dots"];
+/// Checks if two regular files have the same content, without loading them fully.
+pub fn files_match_content(a_path: &Path, b_path: &Path) -> Result<bool> {
+    let a = fs::File::open(a_path)?;
+    let b = fs::File::open(b_path)?;
+
+    let a_len = a.metadata()?.len();
+    let b_len = b.metadata()?.len();
+
+    fn content_match(a: impl Read, b: impl Read) -> io::Result<bool> {
+        let mut a = BufReader::new(a);
+        let mut b = BufReader::new(b);
+
+        loop {
+            let slice_a = a.fill_buf()?;
+            let slice_b = b.fill_buf()?;
+            let len_a = slice_a.len();
+            let len_b = slice_b.len();
+
+            if len_a == 0 || len_b == 0 {
+                assert_eq!(len_a, len_b, "should check len before, or arithmetic bug");
+                return Ok(true);
+            }
+
+            let min = len_a.min(len_b);
+
+            if slice_a[..min] != slice_b[..min] {
+                return Ok(false);
+            }
+
+            a.consume(min);
+            b.consume(min);
+        }
+    }
+
+    Ok(a_len == b_len && content_match(a, b)?)
+}
+
+/// Reads the node at `path` without its children, `None` if there's nothing there.
+pub fn read_node(path: &Path) -> Result<Option<FsTree>> {
+    if !try_exists(path)? {
+        return Ok(None);
+    }
+
+    let node = match read_file_type(path)? {
+        FileType::Regular => FsTree::Regular,
+        FileType::Directory => FsTree::new_dir(),
+        FileType::Symlink => FsTree::Symlink(fs::read_link(path)?),
+    };
+
+    Ok(Some(node))
+}
 
+/// Names the dotfiles folder may have, inside of the home folder.
+pub const DOTFILES_FOLDER_CANDIDATES: &[&str] = &["dotfiles", ".dotfiles", "dots", ".dots"];
+
+pub fn find_dotfiles_folder(home_dir: &Path) -> Result<PathBuf> {
     let mut found: Vec<PathBuf> = Vec::new();
 
-    for candidate in CANDIDATES {
+    for candidate in DOTFILES_FOLDER_CANDIDATES {
         let path = home_dir.join(candidate);
         if try_exists(&path)? {
             found.push(path);
@@ -59,7 +117,7 @@ pub fn find_dotfiles_folder(home_dir: &Path) -> Result<PathBuf> {
     match found.len() {
         0 => Err(eyre!(
             "No dotfiles folder found, please create one. Tried: {}",
-            CANDIDATES.join(", ")
+            DOTFILES_FOLDER_CANDIDATES.join(", ")
         )),
         1 => Ok(found.remove(0)),
         _ => Err(eyre!(
@@ -73,6 +131,23 @@ pub fn find_dotfiles_folder(home_dir: &Path) -> Result<PathBuf> {
     }
 }
 
+/// Names of every group in the dotfiles folder, sorted.
+pub fn list_groups(dotfiles_folder: &Path) -> Result<Vec<String>> {
+    let mut groups = vec![];
+
+    for entry in fs::read_dir(dotfiles_folder)? {
+        let entry = entry?;
+        let name = entry.file_name().to_string_lossy().into_owned();
+
+        if !name.starts_with('.') && read_file_type(entry.path())? == FileType::Directory {
+            groups.push(name);
+        }
+    }
+
+    groups.sort();
+    Ok(groups)
+}
+
 pub fn get_home_dir() -> Result<PathBuf> {
     let home_env_var = env::var_os("HOME")
         .ok_or_eyre("Failed to read user's home directory, try setting $HOME")?;
@@ -97,6 +172,43 @@ pub fn create_symlink(link_location: &Path, original: &Path) -> Result<()> {
     })
 }
 
+/// Points the symlink at `link_location` to `original`, by renaming a new symlink over it, so
+/// the path never goes missing.
+pub fn replace_symlink(link_location: &Path, original: &Path) -> Result<()> {
+    let file_name = link_location
+        .file_name()
+        .ok_or_eyre("symlink location has no file name")?;
+    let temporary = link_location.with_file_name(format!(
+        "{TEMPORARY_PREFIX}{}-{}",
+        process::id(),
+        file_name.to_string_lossy(),
+    ));
+
+    create_symlink(&temporary, original)?;
+    if let Err(err) = fs::rename(&temporary, link_location) {
+        fs::remove_file(&temporary)?;
+        return Err(err.into());
+    }
+    Ok(())
+}
+
+/// Path that `link --backup` moves `path` to, `<name>.dotin-backup-<suffix>`.
+pub fn backup_path(path: &Path, suffix: &str) -> PathBuf {
+    let mut file_name = path.file_name().unwrap_or_default().to_owned();
+    file_name.push(".dotin-backup-");
+    file_name.push(suffix);
+    path.with_file_name(file_name)
+}
+
+/// The default suffix for backups, the current UNIX timestamp.
+pub fn default_backup_suffix() -> String {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|duration| duration.as_secs())
+        .unwrap_or_default()
+        .to_string()
+}
+
 /// Creates a relative target path for a symlink at `link_location` pointing to `target_path`.
 pub fn create_relative_symlink_target_path(link_location: &Path, target_path: &Path) -> PathBuf {
     let link_parent = link_location.parent().unwrap_or(Path::new("."));
@@ -123,6 +235,68 @@ fn relative_path_from(path: &Path, base: &Path) -> PathBuf {
         })
 }
 
+/// The target for a symlink moved from `old_location` to `new_location`, so it still resolves to
+/// the same file, absolute targets are kept.
+pub fn rewrite_symlink_target(target: &Path, old_location: &Path, new_location: &Path) -> PathBuf {
+    if target.is_absolute() {
+        return target.to_owned();
+    }
+
+    let old_parent = old_location.parent().unwrap_or(Path::new("."));
+    let absolute_target = normalize_path(&old_parent.join(target));
+    create_relative_symlink_target_path(new_location, &absolute_target)
+}
+
+/// Checks if `a` and `b`, as targets of a symlink at `link_location`, point to the same file.
+///
+/// Targets are resolved against the link's canonicalized parent directory, `..` components are
+/// resolved by the filesystem (after following symlinks, like it does for the link), and only
+/// lexically for paths that don't exist.
+pub fn are_equivalent_symlink_targets(link_location: &Path, a: &Path, b: &Path) -> bool {
+    if a == b {
+        return true;
+    }
+
+    let link_parent = link_location.parent().unwrap_or(Path::new("."));
+    let link_parent = fs::canonicalize(link_parent).unwrap_or_else(|_| link_parent.to_owned());
+
+    // Doesn't canonicalize the last component, that would follow the group file if it's a symlink
+    let resolve = |target: &Path| -> PathBuf {
+        let path = link_parent.join(target);
+        let canonicalized = path
+            .parent()
+            .zip(path.file_name())
+            .and_then(|(parent, name)| Some(fs::canonicalize(parent).ok()?.join(name)));
+        canonicalized.unwrap_or_else(|| normalize_path(&path))
+    };
+
+    resolve(a) == resolve(b)
+}
+
+/// Lexically resolves `.` and `..` components, without touching the filesystem.
+pub fn normalize_path(path: &Path) -> PathBuf {
+    let mut normalized = PathBuf::new();
+
+    for component in path.components() {
+        match component {
+            Component::CurDir => {}
+            Component::ParentDir => match normalized.components().next_back() {
+                Some(Component::Normal(_)) => {
+                    normalized.pop();
+                }
+                // `/..` is `/`
+                Some(Component::RootDir) => {}
+                _ => normalized.push(".."),
+            },
+            Component::Prefix(_) | Component::RootDir | Component::Normal(_) => {
+                normalized.push(component);
+            }
+        }
+    }
+
+    normalized
+}
+
 pub fn create_folder_at(folder_path: &Path) -> Result<()> {
     match fs::symlink_metadata(folder_path) {
         Ok(_) => {
@@ -145,6 +319,9 @@ pub fn create_folder_at(folder_path: &Path) -> Result<()> {
     }
 }
 
+/// Prefix of the temporary copies made by moves across devices, followed by the process ID.
+pub const TEMPORARY_PREFIX: &str = ".dotin-tmp-";
+
 pub fn cheap_move_with_fallback(from: &Path, to: &Path) -> Result<()> {
     if let Some(to_parent) = to.parent()
         && !try_exists(to_parent)?
@@ -152,17 +329,13 @@ pub fn cheap_move_with_fallback(from: &Path, to: &Path) -> Result<()> {
         fs::create_dir_all(to_parent)?;
     }
 
+    // what an interrupted move into the same folder left behind
+    remove_leftover_temporaries_at(to.parent().unwrap_or(Path::new(".")))?;
+
     if let Err(err) = fs::rename(from, to) {
         // if renaming (cheapest move) is impossible, try fallback
         if err.kind() == io::ErrorKind::CrossesDevices {
-            if let FileType::Directory = read_file_type(from)? {
-                // dir fallback
-                expensive_folder_copy(from.to_owned(), to.to_owned())?;
-            } else {
-                // non-dir fallback
-                fs::copy(from, to).wrap_err("while trying to move file")?;
-                fs::remove_file(from).wrap_err("removing file after copy (mv operation)")?;
-            }
+            move_across_devices(from, to).wrap_err("while trying to move across devices")?;
         } else {
             return Err(err.into());
         }
@@ -170,32 +343,303 @@ pub fn cheap_move_with_fallback(from: &Path, to: &Path) -> Result<()> {
     Ok(())
 }
 
-fn expensive_folder_copy(from: PathBuf, to: PathBuf) -> Result<()> {
+/// Copies `from` into a temporary sibling of `to`, syncs and verifies the copy, renames it into
+/// place, and only then removes `from`.
+///
+/// If interrupted, the only leftover is the temporary, see [`remove_leftover_temporaries`].
+fn move_across_devices(from: &Path, to: &Path) -> Result<()> {
+    let to_parent = match to.parent() {
+        Some(parent) if !parent.as_os_str().is_empty() => parent,
+        _ => Path::new("."),
+    };
+    let file_name = to.file_name().ok_or_eyre("destination has no file name")?;
+
+    let temporary = to_parent.join(format!(
+        "{TEMPORARY_PREFIX}{}-{}",
+        process::id(),
+        file_name.to_string_lossy(),
+    ));
+
+    let copy_result = expensive_copy(from, &temporary)
+        .and_then(|()| sync_tree(&temporary))
+        .and_then(|()| verify_copy(from, &temporary));
+
+    if let Err(err) = copy_result {
+        if try_exists(&temporary)? {
+            remove_path(&temporary)?;
+        }
+        return Err(err);
+    }
+
+    fs::rename(&temporary, to)?;
+    fs::File::open(to_parent)?.sync_all()?;
+
+    remove_path(from).wrap_err("removing source after copy (mv operation)")
+}
+
+/// Removes temporaries left in the tree at `dir` by interrupted cross-device moves.
+pub fn remove_leftover_temporaries(dir: &Path) -> Result<()> {
+    let tree = FsTree::symlink_read_at(dir)?;
+    let mut removed: Vec<PathBuf> = vec![];
+
+    for (_, relative_path) in tree.iter().min_depth(1) {
+        if removed.iter().any(|path| relative_path.starts_with(path)) {
+            continue;
+        }
+        if is_leftover_temporary(&relative_path) {
+            remove_leftover_temporary(&dir.join(&relative_path))?;
+            removed.push(relative_path);
+        }
+    }
+
+    Ok(())
+}
+
+/// Like [`remove_leftover_temporaries`], but only checks the entries directly inside of `dir`.
+pub fn remove_leftover_temporaries_at(dir: &Path) -> Result<()> {
+    for path in find_leftover_temporaries_at(dir)? {
+        remove_leftover_temporary(&path)?;
+    }
+    Ok(())
+}
+
+/// Temporaries left directly inside of `dir` by interrupted cross-device moves.
+pub fn find_leftover_temporaries_at(dir: &Path) -> Result<Vec<PathBuf>> {
+    let dir = if dir.as_os_str().is_empty() {
+        Path::new(".")
+    } else {
+        dir
+    };
+
+    let mut leftovers = vec![];
+    for entry in fs::read_dir(dir)? {
+        let path = entry?.path();
+        if is_leftover_temporary(&path) {
+            leftovers.push(path);
+        }
+    }
+    Ok(leftovers)
+}
+
+/// A temporary whose process isn't running anymore.
+pub fn is_leftover_temporary(path: &Path) -> bool {
+    let Some(rest) = path
+        .file_name()
+        .and_then(OsStr::to_str)
+        .and_then(|name| name.strip_prefix(TEMPORARY_PREFIX))
+    else {
+        return false;
+    };
+
+    let Some(pid) = rest
+        .split_once('-')
+        .and_then(|(pid, _)| pid.parse::<u32>().ok())
+    else {
+        return false;
+    };
+
+    pid != process::id() && !is_process_running(pid)
+}
+
+/// Checks if a process with `pid` exists, by sending it no signal.
+fn is_process_running(pid: u32) -> bool {
+    // no process can have a PID out of range
+    let Ok(pid) = libc::pid_t::try_from(pid) else {
+        return false;
+    };
+
+    // SAFETY: signal 0 only checks that the process exists and can be signaled
+    if unsafe { libc::kill(pid, 0) } == 0 {
+        return true;
+    }
+
+    // exists, but belongs to another user
+    io::Error::last_os_error().raw_os_error() == Some(libc::EPERM)
+}
+
+fn remove_leftover_temporary(path: &Path) -> Result<()> {
+    remove_path(path)?;
+    println!("Removed {path:?}, left by an interrupted move");
+    Ok(())
+}
+
+/// Removes the group folder once everything was moved out of it, returns `false` if files were
+/// left in it (e.g. skipped conflicts).
+pub fn remove_empty_group_folder(group_dir: &Path) -> Result<bool> {
+    fn remove_empty_dirs(dir: &Path) -> Result<bool> {
+        let mut is_empty = true;
+        for entry in fs::read_dir(dir)? {
+            let path = entry?.path();
+            let removed =
+                read_file_type(&path)? == FileType::Directory && remove_empty_dirs(&path)?;
+            is_empty &= removed;
+        }
+
+        if is_empty {
+            fs::remove_dir(dir)?;
+        }
+        Ok(is_empty)
+    }
+
+    let removed = remove_empty_dirs(group_dir)?;
+    if !removed {
+        println!("WARNING: files were left in {group_dir:?}, so the group folder was kept");
+    }
+    Ok(removed)
+}
+
+/// Removes the file, symlink or directory (recursively) at `path`.
+pub fn remove_path(path: &Path) -> Result<()> {
+    match read_file_type(path)? {
+        FileType::Directory => fs::remove_dir_all(path)?,
+        FileType::Regular | FileType::Symlink => fs::remove_file(path)?,
+    }
+    Ok(())
+}
+
+/// Flushes every file and directory of the tree at `path` to disk.
+fn sync_tree(path: &Path) -> Result<()> {
+    let tree = FsTree::symlink_read_at(path)?;
+
+    for (node, relative_path) in &tree {
+        if !node.is_symlink() {
+            fs::File::open(join_relative(path, &relative_path))?.sync_all()?;
+        }
+    }
+    Ok(())
+}
+
+/// Checks that the copy at `to` has the same structure, symlink targets and contents as `from`.
+fn verify_copy(from: &Path, to: &Path) -> Result<()> {
+    let from_tree = FsTree::symlink_read_at(from)?;
+
+    if from_tree != FsTree::symlink_read_at(to)? {
+        bail!("copy at {to:?} differs from {from:?}");
+    }
+
+    for (node, relative_path) in &from_tree {
+        let (from, to) = (
+            join_relative(from, &relative_path),
+            join_relative(to, &relative_path),
+        );
+        if node.is_regular() && !files_match_content(&from, &to)? {
+            bail!("content of copy at {to:?} differs from {from:?}");
+        }
+    }
+
+    Ok(())
+}
+
+/// Like `Path::join`, but doesn't append a trailing slash for an empty `relative_path`.
+pub fn join_relative(path: &Path, relative_path: &Path) -> PathBuf {
+    if relative_path.as_os_str().is_empty() {
+        path.to_owned()
+    } else {
+        path.join(relative_path)
+    }
+}
+
+/// Copies the tree at `from` to `to` as is, symlinks are recreated (not followed).
+///
+/// Fails before copying anything if the tree contains something other than regular files,
+/// directories and symlinks, like FIFOs and sockets.
+pub fn expensive_copy(from: &Path, to: &Path) -> Result<()> {
     // Use a stack to avoid too-many-files error (this can't ever stack
     // overflow due to Linux's path size limit)
-    let mut stack = Vec::new();
-    stack.push((from, to));
+    let mut stack = vec![(from.to_owned(), to.to_owned())];
+    // Parents come before their children
+    let mut entries = vec![];
 
     while let Some((from, to)) = stack.pop() {
-        if fs::symlink_metadata(&from)?.is_dir() {
-            fs::create_dir_all(&to)?;
-            for entry in fs::read_dir(from)? {
-                let entry = entry?;
-                let path = entry.path();
+        let file_type = read_file_type(&from)?;
+        if file_type == FileType::Directory {
+            for entry in fs::read_dir(&from)? {
+                let path = entry?.path();
                 // Unwrap Safety:
                 //   A path retrieved by readdir always has a file_name
-                let name = path.file_name().unwrap();
-                let dest = to.join(name);
+                let dest = to.join(path.file_name().unwrap());
                 stack.push((path, dest));
             }
-        } else {
-            fs::copy(from, to)?;
+        }
+        entries.push((from, to, file_type));
+    }
+
+    for (from, to, file_type) in &entries {
+        match file_type {
+            FileType::Directory => fs::create_dir(to)?,
+            FileType::Regular => {
+                fs::copy(from, to)?;
+            }
+            FileType::Symlink => symlink(fs::read_link(from)?, to)
+                .wrap_err_with(|| format!("creating symlink at {to:?}"))?,
+        }
+
+        if *file_type != FileType::Directory {
+            report_unpreserved(to, copy_metadata(from, to)?);
+        }
+    }
+
+    // Directory metadata is copied after their contents, which would change it
+    for (from, to, _) in entries.iter().rev() {
+        if read_file_type(to)? == FileType::Directory {
+            report_unpreserved(to, copy_metadata(from, to)?);
         }
     }
 
     Ok(())
 }
 
+/// Copies extended attributes, ownership, mode and timestamps from `from` to `to` (without
+/// following symlinks), returns a description of each one that couldn't be preserved.
+fn copy_metadata(from: &Path, to: &Path) -> Result<Vec<String>> {
+    let metadata = fs::symlink_metadata(from)?;
+    let mut unpreserved = vec![];
+
+    match xattr::list(from) {
+        Ok(names) => {
+            for name in names {
+                let result = xattr::get(from, &name)
+                    .and_then(|value| xattr::set(to, &name, &value.unwrap_or_default()));
+                if let Err(err) = result {
+                    unpreserved.push(format!("extended attribute {name:?} ({err})"));
+                }
+            }
+        }
+        // nothing to preserve if the source filesystem has no xattrs
+        Err(err) if err.kind() == io::ErrorKind::Unsupported => {}
+        Err(err) => unpreserved.push(format!("extended attributes ({err})")),
+    }
+
+    // before the mode, changing the owner might clear the setuid and setgid bits
+    let to_metadata = fs::symlink_metadata(to)?;
+    if (to_metadata.uid(), to_metadata.gid()) != (metadata.uid(), metadata.gid())
+        && let Err(err) = lchown(to, Some(metadata.uid()), Some(metadata.gid()))
+    {
+        unpreserved.push(format!("ownership ({err})"));
+    }
+
+    // symlinks have no mode of their own
+    if !metadata.is_symlink()
+        && let Err(err) = fs::set_permissions(to, metadata.permissions())
+    {
+        unpreserved.push(format!("mode ({err})"));
+    }
+
+    let atime = FileTime::from_last_access_time(&metadata);
+    let mtime = FileTime::from_last_modification_time(&metadata);
+    if let Err(err) = filetime::set_symlink_file_times(to, atime, mtime) {
+        unpreserved.push(format!("timestamps ({err})"));
+    }
+
+    Ok(unpreserved)
+}
+
+fn report_unpreserved(path: &Path, unpreserved: Vec<String>) {
+    for what in unpreserved {
+        println!("WARNING: couldn't preserve {what} of {path:?}");
+    }
+}
+
 type PathTrieMap = IndexMap<OsString, PathTrie, rapidhash::fast::RandomState>;
 
 #[derive(Default)]
@@ -333,9 +777,18 @@ pub mod test_utils {
 
 #[cfg(test)]
 mod tests {
-    use std::path::Path;
+    use std::{os::unix::fs::PermissionsExt, path::Path, process};
+
+    use filetime::FileTime;
+    use fs_err as fs;
+    use fs_tree::tree;
+    use pretty_assertions::assert_eq;
 
-    use super::PathTrie;
+    use super::{
+        FsTree, PathTrie, TEMPORARY_PREFIX, are_equivalent_symlink_targets,
+        cheap_move_with_fallback, expensive_copy, move_across_devices, normalize_path,
+        remove_leftover_temporaries, rewrite_symlink_target, test_utils::cd_to_testdir, try_exists,
+    };
 
     #[test]
     fn test_path_trie_contains_ancestor_of() {
@@ -355,4 +808,207 @@ mod tests {
         assert!(!trie.contains_ancestor_of(Path::new("/etc/config")));
         assert!(!trie.contains_ancestor_of(Path::new("/var")));
     }
+
+    #[test]
+    fn test_rewrite_symlink_target() {
+        let rewrite = |target: &str, old: &str, new: &str| {
+            rewrite_symlink_target(Path::new(target), Path::new(old), Path::new(new))
+        };
+
+        assert_eq!(
+            rewrite(
+                "scripts/run",
+                "/home/me/.local/bin",
+                "/home/me/dotfiles/bin/.local/bin"
+            ),
+            Path::new("../../../.local/scripts/run"),
+        );
+        assert_eq!(
+            rewrite(
+                "/etc/hosts",
+                "/home/me/hosts",
+                "/home/me/dotfiles/etc/hosts"
+            ),
+            Path::new("/etc/hosts"),
+        );
+    }
+
+    #[test]
+    fn test_normalize_path() {
+        assert_eq!(normalize_path(Path::new("/a/./b/../c")), Path::new("/a/c"));
+        assert_eq!(normalize_path(Path::new("/a/b/../../..")), Path::new("/"));
+        assert_eq!(
+            normalize_path(Path::new("./../a/../../b")),
+            Path::new("../../b")
+        );
+        assert_eq!(normalize_path(Path::new("a/b/./")), Path::new("a/b"));
+    }
+
+    #[test]
+    fn test_are_equivalent_symlink_targets_with_symlinked_parent() {
+        let (_dropper, test_dir) = cd_to_testdir().unwrap();
+
+        let tree = tree! {
+            real: [
+                sub: []
+            ]
+            linked -> "real/sub"
+            target
+        };
+        tree.write_structure_at(".").unwrap();
+
+        // `..` is resolved from `real/sub`, where `linked` points to
+        let link = test_dir.join("linked/link");
+        let target = test_dir.join("target");
+        assert!(!are_equivalent_symlink_targets(
+            &link,
+            Path::new("../target"),
+            &target
+        ));
+        assert!(are_equivalent_symlink_targets(
+            &link,
+            Path::new("../../target"),
+            &target
+        ));
+    }
+
+    #[test]
+    fn test_expensive_copy_preserves_metadata() {
+        let (_dropper, test_dir) = cd_to_testdir().unwrap();
+        let from = test_dir.join("from");
+        let to = test_dir.join("to");
+
+        fs::create_dir_all(from.join("dir")).unwrap();
+        fs::write(from.join("dir/file"), "content").unwrap();
+
+        let old_time = FileTime::from_unix_time(1_000_000_000, 0);
+        fs::set_permissions(from.join("dir/file"), PermissionsExt::from_mode(0o640)).unwrap();
+        fs::set_permissions(from.join("dir"), PermissionsExt::from_mode(0o750)).unwrap();
+        // not every filesystem supports user xattrs
+        let has_xattr = xattr::set(from.join("dir/file"), "user.dotin", b"value").is_ok();
+        for path in [from.join("dir/file"), from.join("dir")] {
+            filetime::set_file_times(&path, old_time, old_time).unwrap();
+        }
+
+        expensive_copy(&from, &to).unwrap();
+
+        for (path, mode) in [(to.join("dir/file"), 0o640), (to.join("dir"), 0o750)] {
+            let metadata = fs::symlink_metadata(&path).unwrap();
+            assert_eq!(metadata.permissions().mode() & 0o7777, mode, "{path:?}");
+            assert_eq!(
+                FileTime::from_last_modification_time(&metadata),
+                old_time,
+                "{path:?}"
+            );
+        }
+        if has_xattr {
+            let value = xattr::get(to.join("dir/file"), "user.dotin").unwrap();
+            assert_eq!(value.as_deref(), Some(b"value".as_slice()));
+        }
+    }
+
+    #[test]
+    fn test_expensive_copy_recreates_symlinks_and_empty_dirs() {
+        let (_dropper, test_dir) = cd_to_testdir().unwrap();
+
+        let tree = tree! {
+            from: [
+                empty: []
+                file
+                link -> "file"
+                dangling -> "missing"
+            ]
+        };
+        tree.write_structure_at(".").unwrap();
+
+        expensive_copy(&test_dir.join("from"), &test_dir.join("to")).unwrap();
+
+        let copied = FsTree::symlink_read_at("to").unwrap();
+        assert_eq!(copied, tree.children().unwrap()[Path::new("from")]);
+    }
+
+    #[test]
+    fn test_expensive_copy_refuses_fifos_up_front() {
+        let (_dropper, test_dir) = cd_to_testdir().unwrap();
+
+        fs::create_dir_all("from/dir").unwrap();
+        fs::write("from/dir/file", "").unwrap();
+        let status = process::Command::new("mkfifo")
+            .arg("from/fifo")
+            .status()
+            .unwrap();
+        assert!(status.success());
+
+        let result = expensive_copy(&test_dir.join("from"), &test_dir.join("to"));
+
+        assert!(result.is_err());
+        assert!(!try_exists("to").unwrap());
+    }
+
+    #[test]
+    fn test_move_across_devices() {
+        let (_dropper, test_dir) = cd_to_testdir().unwrap();
+
+        let tree = tree! {
+            from: [
+                dir: [
+                    file
+                ]
+                link -> "dir/file"
+            ]
+        };
+        tree.write_structure_at(".").unwrap();
+        fs::write("from/dir/file", "content").unwrap();
+        fs::create_dir("dest").unwrap();
+
+        move_across_devices(&test_dir.join("from"), &test_dir.join("dest/to")).unwrap();
+
+        assert!(!try_exists("from").unwrap());
+        let moved = FsTree::symlink_read_at("dest").unwrap();
+        let expected = tree! {
+            to: [
+                dir: [
+                    file
+                ]
+                link -> "dir/file"
+            ]
+        };
+        assert_eq!(moved, expected);
+        assert_eq!(fs::read_to_string("dest/to/dir/file").unwrap(), "content");
+    }
+
+    #[test]
+    fn test_remove_leftover_temporaries() {
+        let (_dropper, test_dir) = cd_to_testdir().unwrap();
+
+        // PIDs never get this high, so this process isn't running
+        let leftover = format!("{TEMPORARY_PREFIX}4294967295-config");
+        let in_progress = format!("{TEMPORARY_PREFIX}{}-config", process::id());
+        // the parent process (the test runner) is running too
+        let of_running_process = format!(
+            "{TEMPORARY_PREFIX}{}-config",
+            std::os::unix::process::parent_id()
+        );
+
+        fs::create_dir_all(test_dir.join("group/.config").join(&leftover)).unwrap();
+        fs::write(test_dir.join("group/.config").join(&leftover).join("a"), "").unwrap();
+        fs::write(test_dir.join("group").join(&in_progress), "").unwrap();
+        fs::write(test_dir.join("group").join(&of_running_process), "").unwrap();
+
+        remove_leftover_temporaries(&test_dir.join("group")).unwrap();
+
+        assert!(!try_exists(test_dir.join("group/.config").join(&leftover)).unwrap());
+        assert!(try_exists(test_dir.join("group").join(&in_progress)).unwrap());
+        assert!(try_exists(test_dir.join("group").join(&of_running_process)).unwrap());
+
+        // moves clean up their destination folder, like after an interrupted discard
+        fs::create_dir(test_dir.join("home")).unwrap();
+        fs::write(test_dir.join("home").join(&leftover), "").unwrap();
+        cheap_move_with_fallback(
+            &test_dir.join("group").join(&in_progress),
+            &test_dir.join("home/config"),
+        )
+        .unwrap();
+        assert!(!try_exists(test_dir.join("home").join(&leftover)).unwrap());
+    }
 }
@@ -52,3 +52,80 @@ fn config_without_init_prints_existing_config_path() {
         "stdout = {stdout:?}"
     );
 }
+
+#[test]
+fn save_base_persists_the_base_folder_for_later_commands() {
+    let home = tempdir().unwrap();
+    let base = tempdir().unwrap();
+    fs::create_dir(home.path().join("dotfiles")).unwrap();
+    fs::write(base.path().join("nginx.conf"), "").unwrap();
+
+    cargo_bin_cmd!("dotin")
+        .current_dir(base.path())
+        .env("HOME", home.path())
+        .env_remove("XDG_STATE_HOME")
+        .args(["import", "nginx", "nginx.conf", "--base"])
+        .arg(base.path())
+        .arg("--save-base")
+        .assert()
+        .success();
+
+    assert!(base.path().join("nginx.conf").is_symlink());
+    let config = fs::read_to_string(home.path().join(".config/dotin/config.toml")).unwrap();
+    assert!(
+        config.contains(&format!("nginx = {:?}", base.path())),
+        "config = {config}"
+    );
+
+    // no `--base` needed anymore
+    cargo_bin_cmd!("dotin")
+        .env("HOME", home.path())
+        .env_remove("XDG_STATE_HOME")
+        .args(["unlink", "nginx"])
+        .assert()
+        .success();
+
+    assert!(!base.path().join("nginx.conf").exists());
+}
+
+#[test]
+fn save_base_is_skipped_when_the_command_fails() {
+    let home = tempdir().unwrap();
+    let base = tempdir().unwrap();
+    fs::create_dir(home.path().join("dotfiles")).unwrap();
+
+    cargo_bin_cmd!("dotin")
+        .current_dir(base.path())
+        .env("HOME", home.path())
+        .env_remove("XDG_STATE_HOME")
+        .args(["import", "nginx", "missing.conf", "--base"])
+        .arg(base.path())
+        .arg("--save-base")
+        .assert()
+        .failure();
+
+    assert!(!home.path().join(".config/dotin/config.toml").exists());
+}
+
+#[test]
+fn save_base_is_skipped_when_discard_retires_the_group() {
+    let home = tempdir().unwrap();
+    let base = tempdir().unwrap();
+    fs::create_dir_all(home.path().join("dotfiles/nginx")).unwrap();
+    fs::write(home.path().join("dotfiles/nginx/nginx.conf"), "").unwrap();
+
+    cargo_bin_cmd!("dotin")
+        .env("HOME", home.path())
+        .env_remove("XDG_STATE_HOME")
+        .args(["discard", "--all", "nginx", "--base"])
+        .arg(base.path())
+        .arg("--save-base")
+        .assert()
+        .success();
+
+    assert!(base.path().join("nginx.conf").is_file());
+    assert!(!home.path().join("dotfiles/nginx").exists());
+    let config_path = home.path().join(".config/dotin/config.toml");
+    let config = fs::read_to_string(&config_path).unwrap_or_default();
+    assert!(!config.contains("nginx"), "config = {config}");
+}
@@ -0,0 +1,63 @@
+use assert_cmd::cargo::cargo_bin_cmd;
+use fs_err as fs;
+use tempfile::tempdir;
+
+#[test]
+fn diff_shows_copies_and_quiet_sets_the_exit_code() {
+    let home = tempdir().unwrap();
+    fs::create_dir_all(home.path().join("dotfiles/zsh")).unwrap();
+    fs::write(home.path().join("dotfiles/zsh/.zshrc"), "repo\n").unwrap();
+    fs::write(home.path().join(".zshrc"), "copy\n").unwrap();
+
+    let output = cargo_bin_cmd!("dotin")
+        .current_dir(home.path())
+        .env("HOME", home.path())
+        .env_remove("XDG_STATE_HOME")
+        .args(["diff", "zsh"])
+        .assert()
+        .success();
+    let stdout = String::from_utf8_lossy(&output.get_output().stdout);
+    assert!(stdout.contains("text differs: \".zshrc\""), "{stdout}");
+    assert!(stdout.contains("-copy\n+repo\n"), "{stdout}");
+
+    let output = cargo_bin_cmd!("dotin")
+        .current_dir(home.path())
+        .env("HOME", home.path())
+        .env_remove("XDG_STATE_HOME")
+        .args(["diff", "--quiet", "zsh", ".zshrc"])
+        .assert()
+        .code(1);
+    assert!(output.get_output().stdout.is_empty());
+
+    fs::remove_file(home.path().join(".zshrc")).unwrap();
+    cargo_bin_cmd!("dotin")
+        .current_dir(home.path())
+        .env("HOME", home.path())
+        .env_remove("XDG_STATE_HOME")
+        .args(["link", "zsh"])
+        .assert()
+        .success();
+
+    cargo_bin_cmd!("dotin")
+        .current_dir(home.path())
+        .env("HOME", home.path())
+        .env_remove("XDG_STATE_HOME")
+        .args(["diff", "--quiet"])
+        .assert()
+        .code(0);
+}
+
+#[test]
+fn quiet_diff_exits_with_2_on_errors() {
+    let home = tempdir().unwrap();
+    fs::create_dir(home.path().join("dotfiles")).unwrap();
+
+    let output = cargo_bin_cmd!("dotin")
+        .current_dir(home.path())
+        .env("HOME", home.path())
+        .env_remove("XDG_STATE_HOME")
+        .args(["diff", "--quiet", "missing"])
+        .assert()
+        .code(2);
+    assert!(output.get_output().stdout.is_empty());
+}
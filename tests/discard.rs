@@ -0,0 +1,43 @@
+use assert_cmd::cargo::cargo_bin_cmd;
+use fs_err as fs;
+use tempfile::tempdir;
+
+#[test]
+fn discard_all_retires_the_group() {
+    let home = tempdir().unwrap();
+    fs::create_dir(home.path().join("dotfiles")).unwrap();
+    fs::create_dir_all(home.path().join(".config/dotin")).unwrap();
+    fs::create_dir_all(home.path().join(".config/nvim")).unwrap();
+    fs::write(home.path().join(".config/nvim/init.lua"), "init\n").unwrap();
+    fs::write(home.path().join(".nvimrc"), "rc\n").unwrap();
+    fs::write(
+        home.path().join(".config/dotin/config.toml"),
+        "[variables]\n\n[permissions.nvim]\n\".nvimrc\" = \"0600\"\n",
+    )
+    .unwrap();
+
+    cargo_bin_cmd!("dotin")
+        .current_dir(home.path())
+        .env("HOME", home.path())
+        .env_remove("XDG_STATE_HOME")
+        .args(["import", "nvim", ".config/nvim/init.lua", ".nvimrc"])
+        .assert()
+        .success();
+    assert!(home.path().join(".nvimrc").is_symlink());
+
+    cargo_bin_cmd!("dotin")
+        .current_dir(home.path())
+        .env("HOME", home.path())
+        .env_remove("XDG_STATE_HOME")
+        .args(["discard", "--all", "nvim"])
+        .assert()
+        .success();
+
+    assert!(!home.path().join("dotfiles/nvim").exists());
+    assert!(!home.path().join(".nvimrc").is_symlink());
+    let content = fs::read_to_string(home.path().join(".config/nvim/init.lua")).unwrap();
+    assert_eq!(content, "init\n");
+
+    let config = fs::read_to_string(home.path().join(".config/dotin/config.toml")).unwrap();
+    assert_eq!(config, "[variables]\n");
+}
@@ -0,0 +1,144 @@
+use assert_cmd::cargo::cargo_bin_cmd;
+use fs_err as fs;
+use tempfile::tempdir;
+
+#[test]
+fn failing_pre_link_hook_aborts_unless_hooks_are_disabled() {
+    let home = tempdir().unwrap();
+    fs::create_dir_all(home.path().join("dotfiles/shell")).unwrap();
+    fs::write(home.path().join("dotfiles/shell/.bashrc"), "").unwrap();
+    fs::write(
+        home.path().join("dotfiles/dotin.toml"),
+        "[hooks.shell]\npre-link = \"exit 1\"\n",
+    )
+    .unwrap();
+
+    cargo_bin_cmd!("dotin")
+        .env("HOME", home.path())
+        .env_remove("XDG_STATE_HOME")
+        .args(["link", "shell"])
+        .assert()
+        .failure();
+
+    assert!(!home.path().join(".bashrc").exists());
+
+    cargo_bin_cmd!("dotin")
+        .env("HOME", home.path())
+        .env_remove("XDG_STATE_HOME")
+        .args(["link", "--no-hooks", "shell"])
+        .assert()
+        .success();
+
+    assert!(home.path().join(".bashrc").is_symlink());
+}
+
+#[test]
+fn failing_pre_link_hook_of_a_later_group_links_nothing() {
+    let home = tempdir().unwrap();
+    fs::create_dir_all(home.path().join("dotfiles/shell")).unwrap();
+    fs::create_dir_all(home.path().join("dotfiles/git")).unwrap();
+    fs::write(home.path().join("dotfiles/shell/.bashrc"), "").unwrap();
+    fs::write(home.path().join("dotfiles/git/.gitconfig"), "").unwrap();
+    fs::write(
+        home.path().join("dotfiles/dotin.toml"),
+        "[hooks.shell]\npost-link = \"touch ../post-link-ran\"\n\n[hooks.git]\npre-link = \"exit 1\"\n",
+    )
+    .unwrap();
+
+    cargo_bin_cmd!("dotin")
+        .env("HOME", home.path())
+        .env_remove("XDG_STATE_HOME")
+        .args(["link", "shell", "git"])
+        .assert()
+        .failure();
+
+    assert!(!home.path().join(".bashrc").exists());
+    assert!(!home.path().join("dotfiles/post-link-ran").exists());
+}
+
+#[test]
+fn post_link_hook_receives_linked_paths() {
+    let home = tempdir().unwrap();
+    fs::create_dir_all(home.path().join("dotfiles/shell")).unwrap();
+    fs::write(home.path().join("dotfiles/shell/.bashrc"), "").unwrap();
+    fs::write(
+        home.path().join("dotfiles/dotin.toml"),
+        "[hooks.shell]\npost-link = 'echo \"$DOTIN_CHANGED_PATHS\" > ../hook-output'\n",
+    )
+    .unwrap();
+
+    cargo_bin_cmd!("dotin")
+        .env("HOME", home.path())
+        .env_remove("XDG_STATE_HOME")
+        .args(["link", "shell"])
+        .assert()
+        .success();
+
+    let output = fs::read_to_string(home.path().join("dotfiles/hook-output")).unwrap();
+    assert_eq!(
+        output,
+        format!("{}\n", home.path().join(".bashrc").display())
+    );
+}
+
+#[test]
+fn failing_pre_link_hook_on_import_leaves_home_untouched() {
+    let home = tempdir().unwrap();
+    fs::create_dir_all(home.path().join("dotfiles/shell")).unwrap();
+    fs::write(home.path().join(".bashrc"), "").unwrap();
+    fs::write(
+        home.path().join("dotfiles/dotin.toml"),
+        "[hooks.shell]\npre-link = \"exit 1\"\n",
+    )
+    .unwrap();
+
+    cargo_bin_cmd!("dotin")
+        .current_dir(home.path())
+        .env("HOME", home.path())
+        .env_remove("XDG_STATE_HOME")
+        .args(["import", "shell", ".bashrc"])
+        .assert()
+        .failure();
+
+    assert!(home.path().join(".bashrc").is_file());
+    assert!(!home.path().join(".bashrc").is_symlink());
+    assert!(!home.path().join("dotfiles/shell/.bashrc").exists());
+}
+
+#[test]
+fn unlink_runs_every_pre_unlink_hook_first() {
+    let home = tempdir().unwrap();
+    fs::create_dir_all(home.path().join("dotfiles/shell")).unwrap();
+    fs::create_dir_all(home.path().join("dotfiles/git")).unwrap();
+    fs::write(home.path().join("dotfiles/shell/.bashrc"), "").unwrap();
+    fs::write(home.path().join("dotfiles/git/.gitconfig"), "").unwrap();
+    fs::write(
+        home.path().join("dotfiles/dotin.toml"),
+        indoc::indoc! {r#"
+            [hooks.shell]
+            pre-unlink = "echo pre-shell >> ../log"
+            post-unlink = "echo post-shell >> ../log"
+
+            [hooks.git]
+            pre-unlink = "echo pre-git >> ../log"
+            post-unlink = "echo post-git >> ../log"
+        "#},
+    )
+    .unwrap();
+
+    cargo_bin_cmd!("dotin")
+        .env("HOME", home.path())
+        .env_remove("XDG_STATE_HOME")
+        .args(["link", "shell", "git"])
+        .assert()
+        .success();
+    cargo_bin_cmd!("dotin")
+        .env("HOME", home.path())
+        .env_remove("XDG_STATE_HOME")
+        .args(["unlink", "shell", "git"])
+        .assert()
+        .success();
+
+    let log = fs::read_to_string(home.path().join("dotfiles/log")).unwrap();
+    assert_eq!(log, "pre-shell\npre-git\npost-shell\npost-git\n");
+}
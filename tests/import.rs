@@ -0,0 +1,59 @@
+use assert_cmd::cargo::cargo_bin_cmd;
+use fs_err as fs;
+use tempfile::tempdir;
+
+#[test]
+fn auto_import_groups_files_by_the_catalog() {
+    let home = tempdir().unwrap();
+    fs::create_dir(home.path().join("dotfiles")).unwrap();
+    fs::create_dir_all(home.path().join(".config/nvim")).unwrap();
+    fs::create_dir_all(home.path().join(".config/dotin")).unwrap();
+    fs::write(home.path().join(".config/nvim/init.lua"), "").unwrap();
+    fs::write(home.path().join(".zshrc"), "").unwrap();
+    fs::write(home.path().join(".myrc"), "").unwrap();
+    fs::write(
+        home.path().join(".config/dotin/config.toml"),
+        "[catalog]\n\".myrc\" = \"mine\"\n",
+    )
+    .unwrap();
+
+    let assert = cargo_bin_cmd!("dotin")
+        .current_dir(home.path())
+        .env("HOME", home.path())
+        .env_remove("XDG_STATE_HOME")
+        .args(["import", "--auto", ".config/nvim", ".zshrc", ".myrc"])
+        .assert()
+        .success();
+
+    let stdout = String::from_utf8_lossy(&assert.get_output().stdout);
+    assert!(
+        stdout.contains("Importing by catalog group:"),
+        "stdout = {stdout}"
+    );
+    assert!(stdout.contains("zsh: [\".zshrc\"]"), "stdout = {stdout}");
+
+    let dotfiles = home.path().join("dotfiles");
+    assert!(dotfiles.join("nvim/.config/nvim/init.lua").exists());
+    assert!(dotfiles.join("zsh/.zshrc").exists());
+    assert!(dotfiles.join("mine/.myrc").exists());
+    assert!(home.path().join(".zshrc").is_symlink());
+}
+
+#[test]
+fn auto_import_refuses_unknown_paths_before_moving_anything() {
+    let home = tempdir().unwrap();
+    fs::create_dir(home.path().join("dotfiles")).unwrap();
+    fs::write(home.path().join(".zshrc"), "").unwrap();
+    fs::write(home.path().join(".unknownrc"), "").unwrap();
+
+    cargo_bin_cmd!("dotin")
+        .current_dir(home.path())
+        .env("HOME", home.path())
+        .env_remove("XDG_STATE_HOME")
+        .args(["import", "--auto", ".zshrc", ".unknownrc"])
+        .assert()
+        .failure();
+
+    assert!(!home.path().join(".zshrc").is_symlink());
+    assert!(!home.path().join("dotfiles/zsh").exists());
+}
@@ -0,0 +1,115 @@
+use assert_cmd::cargo::cargo_bin_cmd;
+use fs_err as fs;
+use tempfile::tempdir;
+
+#[test]
+fn interactive_import_keeps_the_home_version() {
+    let home = tempdir().unwrap();
+    fs::create_dir_all(home.path().join("dotfiles/shell")).unwrap();
+    fs::write(home.path().join("dotfiles/shell/.bashrc"), "group\n").unwrap();
+    fs::write(home.path().join(".bashrc"), "home\n").unwrap();
+
+    let assert = cargo_bin_cmd!("dotin")
+        .current_dir(home.path())
+        .env("HOME", home.path())
+        .env_remove("XDG_STATE_HOME")
+        .args(["import", "--interactive", "shell", ".bashrc"])
+        .write_stdin("x\nh\n")
+        .assert()
+        .success();
+
+    let stdout = String::from_utf8_lossy(&assert.get_output().stdout);
+    assert!(stdout.contains("-home\n+group\n"), "stdout = {stdout}");
+    assert!(stdout.contains("Invalid choice \"x\""), "stdout = {stdout}");
+
+    let content = fs::read_to_string(home.path().join("dotfiles/shell/.bashrc")).unwrap();
+    assert_eq!(content, "home\n");
+    assert!(home.path().join(".bashrc").is_symlink());
+}
+
+#[test]
+fn interactive_discard_renames_the_home_version() {
+    let home = tempdir().unwrap();
+    fs::create_dir_all(home.path().join("dotfiles/shell")).unwrap();
+    fs::write(home.path().join("dotfiles/shell/.bashrc"), "group\n").unwrap();
+    fs::write(home.path().join(".bashrc"), "home\n").unwrap();
+
+    cargo_bin_cmd!("dotin")
+        .current_dir(home.path())
+        .env("HOME", home.path())
+        .env_remove("XDG_STATE_HOME")
+        .args(["discard", "--interactive", "shell", ".bashrc"])
+        .write_stdin("r\n")
+        .assert()
+        .success();
+
+    let content = fs::read_to_string(home.path().join(".bashrc")).unwrap();
+    assert_eq!(content, "group\n");
+    assert!(!home.path().join("dotfiles/shell/.bashrc").exists());
+
+    let renamed = fs::read_dir(home.path())
+        .unwrap()
+        .map(|entry| entry.unwrap().path())
+        .find(|path| {
+            let name = path.file_name().unwrap().to_string_lossy();
+            name.starts_with(".bashrc.dotin-backup-")
+        })
+        .unwrap();
+    assert_eq!(fs::read_to_string(renamed).unwrap(), "home\n");
+}
+
+#[test]
+fn interactive_import_renames_the_group_version_outside_of_the_group() {
+    let home = tempdir().unwrap();
+    fs::create_dir_all(home.path().join("dotfiles/shell")).unwrap();
+    fs::write(home.path().join("dotfiles/shell/.bashrc"), "group\n").unwrap();
+    fs::write(home.path().join(".bashrc"), "home\n").unwrap();
+
+    cargo_bin_cmd!("dotin")
+        .current_dir(home.path())
+        .env("HOME", home.path())
+        .env_remove("XDG_STATE_HOME")
+        .args(["import", "--interactive", "shell", ".bashrc"])
+        .write_stdin("r\n")
+        .assert()
+        .success();
+
+    let content = fs::read_to_string(home.path().join("dotfiles/shell/.bashrc")).unwrap();
+    assert_eq!(content, "home\n");
+    assert!(home.path().join(".bashrc").is_symlink());
+
+    let group_entries = fs::read_dir(home.path().join("dotfiles/shell"))
+        .unwrap()
+        .map(|entry| entry.unwrap().file_name())
+        .collect::<Vec<_>>();
+    assert_eq!(group_entries, [".bashrc"]);
+
+    let renamed = fs::read_dir(home.path())
+        .unwrap()
+        .map(|entry| entry.unwrap().path())
+        .find(|path| {
+            let name = path.file_name().unwrap().to_string_lossy();
+            name.starts_with(".bashrc.dotin-backup-")
+        })
+        .unwrap();
+    assert_eq!(fs::read_to_string(renamed).unwrap(), "group\n");
+}
+
+#[test]
+fn conflicts_fail_without_interactive() {
+    let home = tempdir().unwrap();
+    fs::create_dir_all(home.path().join("dotfiles/shell")).unwrap();
+    fs::write(home.path().join("dotfiles/shell/.bashrc"), "group\n").unwrap();
+    fs::write(home.path().join(".bashrc"), "home\n").unwrap();
+
+    cargo_bin_cmd!("dotin")
+        .current_dir(home.path())
+        .env("HOME", home.path())
+        .env_remove("XDG_STATE_HOME")
+        .args(["import", "shell", ".bashrc"])
+        .assert()
+        .failure();
+
+    let content = fs::read_to_string(home.path().join(".bashrc")).unwrap();
+    assert_eq!(content, "home\n");
+}
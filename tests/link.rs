@@ -0,0 +1,61 @@
+use assert_cmd::cargo::cargo_bin_cmd;
+use fs_err as fs;
+use tempfile::tempdir;
+
+#[test]
+fn link_backup_accepts_optional_suffix() {
+    let home = tempdir().unwrap();
+    fs::create_dir_all(home.path().join("dotfiles/shell")).unwrap();
+    fs::write(home.path().join("dotfiles/shell/.bashrc"), "").unwrap();
+    fs::write(home.path().join("dotfiles/shell/.profile"), "").unwrap();
+    fs::write(home.path().join(".bashrc"), "from home").unwrap();
+
+    cargo_bin_cmd!("dotin")
+        .env("HOME", home.path())
+        .env_remove("XDG_STATE_HOME")
+        .args(["link", "--backup=old", "shell"])
+        .assert()
+        .success();
+
+    assert!(home.path().join(".bashrc.dotin-backup-old").exists());
+
+    fs::remove_file(home.path().join(".profile")).unwrap();
+    fs::write(home.path().join(".profile"), "from home").unwrap();
+
+    // Without a value, `shell` is still parsed as the group name
+    cargo_bin_cmd!("dotin")
+        .env("HOME", home.path())
+        .env_remove("XDG_STATE_HOME")
+        .args(["link", "--backup", "shell"])
+        .assert()
+        .success();
+
+    let backups = fs::read_dir(home.path())
+        .unwrap()
+        .filter(|entry| {
+            let name = entry.as_ref().unwrap().file_name();
+            name.to_string_lossy().starts_with(".profile.dotin-backup-")
+        })
+        .count();
+    assert_eq!(backups, 1);
+}
+
+#[test]
+fn unlink_removes_directories_created_by_link() {
+    let home = tempdir().unwrap();
+    fs::create_dir_all(home.path().join("dotfiles/i3/.config/i3")).unwrap();
+    fs::write(home.path().join("dotfiles/i3/.config/i3/config"), "").unwrap();
+
+    for command in ["link", "unlink"] {
+        cargo_bin_cmd!("dotin")
+            .env("HOME", home.path())
+            .env_remove("XDG_STATE_HOME")
+            .args([command, "i3"])
+            .assert()
+            .success();
+    }
+
+    assert!(!home.path().join(".config").exists());
+    let state = fs::read_to_string(home.path().join(".local/state/dotin/state.toml")).unwrap();
+    assert!(!state.contains("i3"), "state = {state:?}");
+}
@@ -0,0 +1,107 @@
+use assert_cmd::cargo::cargo_bin_cmd;
+use fs_err as fs;
+use tempfile::tempdir;
+
+#[test]
+fn rename_group_relinks_and_renames_config_entries() {
+    let home = tempdir().unwrap();
+    fs::create_dir_all(home.path().join("dotfiles/wm/.config/i3")).unwrap();
+    fs::create_dir_all(home.path().join(".config/dotin")).unwrap();
+    fs::write(home.path().join("dotfiles/wm/.config/i3/config"), "").unwrap();
+    fs::write(
+        home.path().join(".config/dotin/config.toml"),
+        "# window manager\n[hooks.wm]\npost-link = \"true\"\n",
+    )
+    .unwrap();
+
+    let dotin = || {
+        let mut command = cargo_bin_cmd!("dotin");
+        command
+            .current_dir(home.path())
+            .env("HOME", home.path())
+            .env_remove("XDG_STATE_HOME");
+        command
+    };
+
+    dotin().args(["link", "wm"]).assert().success();
+    dotin()
+        .args(["rename-group", "wm", "i3"])
+        .assert()
+        .success();
+
+    let link = home.path().join(".config/i3/config");
+    assert_eq!(
+        fs::read_link(&link).unwrap(),
+        std::path::Path::new("../../dotfiles/i3/.config/i3/config")
+    );
+    assert!(!home.path().join("dotfiles/wm").exists());
+
+    let config = fs::read_to_string(home.path().join(".config/dotin/config.toml")).unwrap();
+    assert_eq!(
+        config,
+        "# window manager\n[hooks.i3]\npost-link = \"true\"\n"
+    );
+
+    // refuses to rename over an existing group
+    fs::create_dir(home.path().join("dotfiles/wm")).unwrap();
+    dotin()
+        .args(["rename-group", "wm", "i3"])
+        .assert()
+        .failure();
+
+    // the state was renamed too, so unlink finds the links
+    dotin().args(["unlink", "i3"]).assert().success();
+    assert!(!link.exists());
+}
+
+#[test]
+fn split_and_merge_keep_home_linked() {
+    let home = tempdir().unwrap();
+    for app in ["polybar", "rofi"] {
+        let dir = home.path().join("dotfiles/desktop/.config").join(app);
+        fs::create_dir_all(&dir).unwrap();
+        fs::write(dir.join("config"), app).unwrap();
+    }
+
+    let dotin = || {
+        let mut command = cargo_bin_cmd!("dotin");
+        command
+            .current_dir(home.path())
+            .env("HOME", home.path())
+            .env_remove("XDG_STATE_HOME");
+        command
+    };
+
+    dotin().args(["link", "desktop"]).assert().success();
+    dotin()
+        .args(["split", "desktop", "bar", ".config/polybar"])
+        .assert()
+        .success();
+
+    let polybar = home.path().join(".config/polybar/config");
+    assert_eq!(fs::read_to_string(&polybar).unwrap(), "polybar");
+    assert!(
+        home.path()
+            .join("dotfiles/bar/.config/polybar/config")
+            .exists()
+    );
+    assert!(
+        !home
+            .path()
+            .join("dotfiles/desktop/.config/polybar")
+            .exists()
+    );
+
+    dotin().args(["merge", "bar", "desktop"]).assert().success();
+
+    assert_eq!(fs::read_to_string(&polybar).unwrap(), "polybar");
+    assert!(!home.path().join("dotfiles/bar").exists());
+    assert!(
+        home.path()
+            .join("dotfiles/desktop/.config/polybar/config")
+            .exists()
+    );
+
+    dotin().args(["unlink", "desktop"]).assert().success();
+    assert!(!polybar.exists());
+}